@@ -0,0 +1,79 @@
+//! Compiles the embedded station database into static tables, so that building a
+//! `Trenitalia` instance doesn't have to parse the TSV files
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+#[path = "src/normalize.rs"]
+mod normalize;
+
+#[path = "src/station_tables.rs"]
+mod station_tables;
+
+use station_tables::{
+    Lookups, StationFiles, ALIASES_FILE, LEFRECCE_NAMES_FILE, STATIONS_FILE, VT_IDS_FILE,
+};
+
+fn read(file: &str) -> String {
+    println!("cargo:rerun-if-changed={}", file);
+    std::fs::read_to_string(file).unwrap_or_else(|e| panic!("{}: {}", file, e))
+}
+
+/// Writes a perfect-hash map from the keys to the indexes of the stations
+fn write_map(output: &mut String, name: &str, map: &HashMap<String, usize>) {
+    let mut builder = phf_codegen::Map::new();
+    for (key, i) in map {
+        builder.entry(key.as_str(), &i.to_string());
+    }
+    writeln!(
+        output,
+        "static {}: phf::Map<&'static str, usize> = {};",
+        name,
+        builder.build()
+    )
+    .unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/normalize.rs");
+    println!("cargo:rerun-if-changed=src/station_tables.rs");
+    let stations = station_tables::parse(&StationFiles {
+        stations: &read(STATIONS_FILE),
+        aliases: &read(ALIASES_FILE),
+        lefrecce_names: &read(LEFRECCE_NAMES_FILE),
+        vt_ids: &read(VT_IDS_FILE),
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    let lookups = Lookups::new(&stations);
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        "static STATIONS: [EmbeddedStation; {}] = [",
+        stations.len()
+    )
+    .unwrap();
+    for station in &stations {
+        writeln!(
+            output,
+            "    EmbeddedStation {{ id: {:?}, aliases: &{:?}, region_id: {}, position: ({:?}, {:?}), vt_id: {:?}, lefrecce_name: {:?} }},",
+            station.id,
+            station.aliases,
+            station.region_id,
+            station.position.0,
+            station.position.1,
+            station.vt_id,
+            station.lefrecce_name,
+        )
+        .unwrap();
+    }
+    writeln!(output, "];").unwrap();
+    write_map(&mut output, "BY_NAME", &lookups.by_name);
+    write_map(&mut output, "BY_ID", &lookups.by_id);
+    write_map(&mut output, "BY_VT_ID", &lookups.by_vt_id);
+    write_map(&mut output, "BY_LEFRECCE_NAME", &lookups.by_lefrecce_name);
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("stations.rs"), output).unwrap();
+}
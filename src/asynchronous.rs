@@ -0,0 +1,362 @@
+//! Asynchronous mirror of the public API, enabled by the `async` feature
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::future::try_join_all;
+
+use crate::types::{BoardEntry, Region, TrainInfo, TrainNumber, TrainStation, TrainTrip};
+use crate::viaggiatreno::SolutionPart;
+use crate::{
+    lefrecce, mapping, viaggiatreno, ConnectionRisk, ConnectionStatus, Error, FixtureTransport,
+    Journey, RemoteStation, TrainEvent, TrainWatcher, Transport, Trenitalia, WatchedTrain,
+};
+
+/// Future returned by an `AsyncTransport`
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<String, Error>> + Send + 'a>>;
+
+/// Asynchronous HTTP client used to reach the ViaggiaTreno and LeFrecce APIs
+pub trait AsyncTransport: Send + Sync {
+    /// Performs a GET request and returns the response body
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a>;
+}
+
+/// Runs a blocking `Transport` on the tokio blocking thread pool.
+/// This is the default asynchronous transport, wrapping the blocking one.
+pub struct BlockingTransport {
+    transport: Arc<dyn Transport>,
+}
+
+impl BlockingTransport {
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        BlockingTransport {
+            transport: Arc::new(transport),
+        }
+    }
+
+    pub(crate) fn from_arc(transport: Arc<dyn Transport>) -> Self {
+        BlockingTransport { transport }
+    }
+}
+
+impl AsyncTransport for BlockingTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        let transport = self.transport.clone();
+        let url = url.to_string();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || transport.get(&url))
+                .await
+                .map_err(|e| Error::Transport(e.to_string()))?
+        })
+    }
+}
+
+impl AsyncTransport for FixtureTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        Box::pin(std::future::ready(Transport::get(self, url)))
+    }
+}
+
+impl<T: AsyncTransport + ?Sized> AsyncTransport for Arc<T> {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        (**self).get(url)
+    }
+}
+
+/// Find trips on LeFrecce, fetching the details of every solution concurrently
+async fn lefrecce_find_trips(
+    trenitalia: &Trenitalia,
+    from: &TrainStation,
+    to: &TrainStation,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> Result<Vec<Journey>, Error> {
+    let (from_name, to_name) = match lefrecce::search_names(from, to) {
+        Some(names) => names,
+        None => return Ok(vec![]),
+    };
+    let url = lefrecce::solutions_url(trenitalia, from_name, to_name, when);
+    let body: Vec<lefrecce::LFSolution> =
+        serde_json::from_str(&trenitalia.http_get_async(&url).await?)?;
+    let urls: Vec<String> = body
+        .iter()
+        .map(|solution| lefrecce::details_url(trenitalia, solution))
+        .collect();
+    let responses = try_join_all(urls.iter().map(|url| trenitalia.http_get_async(url))).await?;
+    let mut result: Vec<Journey> = Vec::new();
+    for (solution, response) in body.iter().zip(responses.iter()) {
+        let details: lefrecce::LFDetailedSolution = serde_json::from_str(response)?;
+        result.extend(Journey::new(
+            lefrecce::trips_from_details(trenitalia, &details)?,
+            solution.minprice,
+        ));
+    }
+    Ok(result)
+}
+
+impl Trenitalia {
+    /// Performs a GET request through the configured asynchronous transport
+    pub(crate) async fn http_get_async(&self, url: &str) -> Result<String, Error> {
+        self.async_transport.get(url).await
+    }
+
+    /// Asynchronous version of `find_trips`.
+    /// The LeFrecce searches that fill the gaps of every solution run concurrently.
+    pub async fn find_trips_async(
+        &self,
+        from: &TrainStation,
+        to: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<Journey>, Error> {
+        let url = viaggiatreno::solutions_url(self, from, to, when)?;
+        let body: mapping::VTJourneySearchResult =
+            serde_json::from_str(&self.http_get_async(&url).await?)?;
+        if body.soluzioni.is_empty() {
+            return lefrecce_find_trips(self, from, to, when).await;
+        }
+        let plans = body
+            .soluzioni
+            .iter()
+            .map(|soluzione| viaggiatreno::plan_solution(self, soluzione, from, to, when))
+            .collect::<Result<Vec<Vec<SolutionPart>>, Error>>()?;
+        let fillings = try_join_all(plans.iter().flatten().filter_map(|part| match part {
+            SolutionPart::Gap(gap) => Some(async move {
+                Ok::<_, Error>(
+                    gap.fill(&lefrecce_find_trips(self, &gap.from, &gap.to, &gap.when).await?),
+                )
+            }),
+            SolutionPart::Trip(_) => None,
+        }))
+        .await?;
+        let mut fillings = fillings.into_iter();
+        let mut result: Vec<Journey> = Vec::new();
+        for plan in plans {
+            let mut train_trips: Vec<TrainTrip> = Vec::new();
+            for part in plan {
+                match part {
+                    SolutionPart::Trip(trip) => train_trips.push(trip),
+                    SolutionPart::Gap(_) => {
+                        train_trips.extend(fillings.next().into_iter().flatten())
+                    }
+                }
+            }
+            result.extend(Journey::new(train_trips, None));
+        }
+        Ok(result)
+    }
+
+    /// Asynchronous version of `find_train_station_online`
+    pub async fn find_train_station_online_async(
+        &self,
+        name: &str,
+    ) -> Result<Option<&TrainStation>, Error> {
+        let url = self.viaggiatreno_url(&format!("autocompletaStazione/{}", name));
+        let response = self.http_get_async(&url).await?;
+        Ok(viaggiatreno::station_from_autocomplete(self, &response))
+    }
+
+    /// Asynchronous version of `station_region`
+    pub async fn station_region_async(&self, station: &TrainStation) -> Result<Region, Error> {
+        let url = viaggiatreno::region_url(self, station)?;
+        viaggiatreno::parse_region(&self.http_get_async(&url).await?)
+    }
+
+    /// Asynchronous version of `station_list`
+    pub async fn station_list_async(&self) -> Result<Vec<RemoteStation>, Error> {
+        let urls: Vec<String> = Region::ALL
+            .iter()
+            .map(|region| viaggiatreno::station_list_url(self, *region))
+            .collect();
+        let responses = try_join_all(urls.iter().map(|url| self.http_get_async(url))).await?;
+        let mut stations: Vec<RemoteStation> = Vec::new();
+        for response in responses {
+            stations.extend(viaggiatreno::parse_station_list(&response)?);
+        }
+        Ok(stations)
+    }
+
+    /// Asynchronous version of `departures`
+    pub async fn departures_async(
+        &self,
+        station: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<BoardEntry>, Error> {
+        self.board_async(viaggiatreno::Board::Departures, station, when)
+            .await
+    }
+
+    /// Asynchronous version of `arrivals`
+    pub async fn arrivals_async(
+        &self,
+        station: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<BoardEntry>, Error> {
+        self.board_async(viaggiatreno::Board::Arrivals, station, when)
+            .await
+    }
+
+    async fn board_async(
+        &self,
+        board: viaggiatreno::Board,
+        station: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<BoardEntry>, Error> {
+        let url = viaggiatreno::board_url(self, board, station, when)?;
+        viaggiatreno::parse_board(self, board, &self.http_get_async(&url).await?)
+    }
+
+    async fn train_info_raw_async(
+        &self,
+        number: u32,
+        candidate: &viaggiatreno::TrainCandidate,
+    ) -> Result<TrainInfo, Error> {
+        let url = viaggiatreno::train_info_url(self, number, candidate)?;
+        let response: mapping::VTTrainStatus =
+            serde_json::from_str(&self.http_get_async(&url).await?)?;
+        TrainInfo::from(&response, self)
+    }
+
+    async fn train_candidates_async(
+        &self,
+        number: u32,
+    ) -> Result<Vec<viaggiatreno::TrainCandidate>, Error> {
+        viaggiatreno::parse_train_candidates(
+            &self
+                .http_get_async(&viaggiatreno::train_search_url(self, number))
+                .await?,
+        )
+    }
+
+    /// Asynchronous version of `train_info`
+    pub async fn train_info_async(&self, number: u32, from: String) -> Result<TrainInfo, Error> {
+        let candidates = self.train_candidates_async(number).await?;
+        let train_of_origination = viaggiatreno::select_train_origin(&candidates, number, &from)?;
+        self.train_info_raw_async(number, train_of_origination)
+            .await
+    }
+
+    /// Asynchronous version of `train_info_calling_at`.
+    /// When more than one train has the same number, their details are fetched concurrently.
+    pub async fn train_info_calling_at_async(
+        &self,
+        number: u32,
+        calling_at: &TrainStation,
+    ) -> Result<TrainInfo, Error> {
+        let candidates = self.train_candidates_async(number).await?;
+        match candidates.len() {
+            1 => self.train_info_raw_async(number, &candidates[0]).await,
+            0 => Err(Error::TrainNotFound(number)),
+            _ => try_join_all(
+                candidates
+                    .iter()
+                    .map(|candidate| self.train_info_raw_async(number, candidate)),
+            )
+            .await?
+            .into_iter()
+            .find(|train_info| {
+                train_info
+                    .stops
+                    .iter()
+                    .any(|stop| stop.station.id == calling_at.id)
+            })
+            .ok_or(Error::AmbiguousTrain(number)),
+        }
+    }
+}
+
+impl TrainTrip {
+    /// Asynchronous version of `get_fare`
+    pub async fn get_fare_async(&self, trenitalia: &Trenitalia) -> Result<Option<f64>, Error> {
+        let (from_name, to_name) = self.lefrecce_names()?;
+        let url = lefrecce::solutions_url(trenitalia, from_name, to_name, &self.departure.1);
+        let body: Vec<lefrecce::LFSolution> =
+            serde_json::from_str(&trenitalia.http_get_async(&url).await?)?;
+        self.fare_from_solutions(&body)
+    }
+}
+
+impl TrainWatcher<'_> {
+    /// Asynchronous version of `poll`. Waiting between two polls is left to the caller
+    pub async fn poll_async(&mut self) -> Result<Vec<TrainEvent>, Error> {
+        let (trenitalia, number, train) = self.start_poll();
+        let info = match train {
+            WatchedTrain::From(origin) => trenitalia.train_info_async(number, origin).await,
+            WatchedTrain::CallingAt(station) => {
+                trenitalia
+                    .train_info_calling_at_async(number, &station)
+                    .await
+            }
+        }?;
+        Ok(self.update(info))
+    }
+}
+
+impl Journey {
+    /// Asynchronous version of `connection_risks`.
+    /// The live status of every leg, and then every fallback, are fetched concurrently.
+    pub async fn connection_risks_async(
+        &self,
+        trenitalia: &Trenitalia,
+        min_buffer: chrono::Duration,
+    ) -> Result<Vec<ConnectionRisk>, Error> {
+        if self.legs().len() < 2 {
+            return Ok(vec![]);
+        }
+        let infos = self.live_infos_async(trenitalia).await?;
+        let mut risks = self.assess_connections(&infos, min_buffer);
+        let fallbacks = try_join_all(risks.iter().map(|risk| async move {
+            if risk.status == ConnectionStatus::Safe {
+                return Ok(None);
+            }
+            let journeys = trenitalia
+                .find_trips_async(
+                    &risk.transfer.arrival.0,
+                    self.destination(),
+                    &risk.expected_arrival,
+                )
+                .await?;
+            Ok::<_, Error>(risk.pick_fallback(journeys))
+        }))
+        .await?;
+        for (risk, fallback) in risks.iter_mut().zip(fallbacks) {
+            risk.fallback = fallback;
+        }
+        Ok(risks)
+    }
+
+    /// Asynchronous version of `replan`
+    pub async fn replan_async(
+        &self,
+        trenitalia: &Trenitalia,
+        now: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<Journey>, Error> {
+        let infos = self.live_infos_async(trenitalia).await?;
+        match self.replan_position(&infos, now) {
+            Some((station, expected_arrival)) => Ok(Self::continuations(
+                trenitalia
+                    .find_trips_async(&station, self.destination(), &expected_arrival)
+                    .await?,
+                &expected_arrival,
+            )),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Fetches the live status of every leg concurrently
+    async fn live_infos_async(
+        &self,
+        trenitalia: &Trenitalia,
+    ) -> Result<Vec<Option<TrainInfo>>, Error> {
+        try_join_all(self.legs().iter().map(|leg| async move {
+            if let TrainNumber::Bus { .. } = leg.train_number {
+                return Ok(None);
+            }
+            crate::journey::optional_live_info(
+                trenitalia
+                    .train_info_calling_at_async(u32::from(&leg.train_number), &leg.departure.0)
+                    .await,
+            )
+        }))
+        .await
+    }
+}
@@ -0,0 +1,126 @@
+//! Offline station autocompletion, backed by a sorted index of the words in the station names
+
+use std::collections::HashMap;
+
+use crate::normalize::normalize;
+use crate::types::{StationMatch, TrainStation};
+
+/// Splits a name into the words of its normal form
+fn words(name: &str) -> Vec<String> {
+    normalize(name)
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// An alias of a station, split in the words of its normal form
+struct IndexedAlias {
+    station: usize,
+    alias: usize,
+    words: Vec<String>,
+    /// Number of letters in the words
+    length: usize,
+}
+
+/// The aliases of every station, and the sorted list of (word, alias index) for every word
+/// of every alias
+pub(crate) struct PrefixIndex {
+    aliases: Vec<IndexedAlias>,
+    entries: Vec<(String, usize)>,
+}
+
+impl PrefixIndex {
+    pub(crate) fn new(stations: &[TrainStation]) -> Self {
+        let aliases: Vec<IndexedAlias> = stations
+            .iter()
+            .enumerate()
+            .flat_map(|(i, station)| {
+                station.aliases.iter().enumerate().map(move |(j, alias)| {
+                    let words = words(alias);
+                    IndexedAlias {
+                        station: i,
+                        alias: j,
+                        length: words.iter().map(|word| word.chars().count()).sum(),
+                        words,
+                    }
+                })
+            })
+            .collect();
+        let mut entries: Vec<(String, usize)> = aliases
+            .iter()
+            .enumerate()
+            .flat_map(|(k, alias)| alias.words.iter().map(move |word| (word.clone(), k)))
+            .collect();
+        entries.sort();
+        entries.dedup();
+        PrefixIndex { aliases, entries }
+    }
+
+    /// Returns the stations with an alias in which every word of `query` starts a word.
+    /// Shorter aliases, which are closer to what has been typed, come first.
+    pub(crate) fn search<'a>(
+        &self,
+        stations: &'a [TrainStation],
+        query: &str,
+        limit: usize,
+    ) -> Vec<StationMatch<'a>> {
+        let query_words = words(query);
+        // The longest word is the most selective one
+        let key = match query_words.iter().max_by_key(|word| word.len()) {
+            Some(key) => key,
+            None => return vec![],
+        };
+        let typed: usize = query_words.iter().map(|word| word.chars().count()).sum();
+        let start = self
+            .entries
+            .partition_point(|(word, _)| word.as_str() < key.as_str());
+        let mut matches: Vec<StationMatch> = Vec::new();
+        // Position in `matches` of the stations found so far
+        let mut found: HashMap<usize, usize> = HashMap::new();
+        for (_, k) in self.entries[start..]
+            .iter()
+            .take_while(|(word, _)| word.starts_with(key.as_str()))
+        {
+            let indexed = &self.aliases[*k];
+            if !query_words.iter().all(|query_word| {
+                indexed
+                    .words
+                    .iter()
+                    .any(|alias_word| alias_word.starts_with(query_word.as_str()))
+            }) {
+                continue;
+            }
+            let station = &stations[indexed.station];
+            let alias = &station.aliases[indexed.alias];
+            let score = (typed as f64 / indexed.length as f64).min(1.0);
+            match found.get(&indexed.station) {
+                Some(&m) if matches[m].score >= score => {}
+                Some(&m) => {
+                    matches[m].alias = alias;
+                    matches[m].score = score;
+                }
+                None => {
+                    found.insert(indexed.station, matches.len());
+                    matches.push(StationMatch {
+                        station,
+                        alias,
+                        score,
+                    });
+                }
+            }
+        }
+        let order = |a: &StationMatch, b: &StationMatch| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.alias.cmp(b.alias))
+                .then_with(|| a.station.id.cmp(&b.station.id))
+        };
+        // Short queries match thousands of stations: only the best ones are sorted
+        if limit > 0 && matches.len() > limit {
+            matches.select_nth_unstable_by(limit - 1, order);
+        }
+        matches.truncate(limit);
+        matches.sort_by(order);
+        matches
+    }
+}
@@ -0,0 +1,67 @@
+//! Generates the station codes from the ViaggiaTreno station list, like `id_gen.py`.
+//! Names are split in words of letters only, so the codes can differ from the script's when
+//! a name has other characters (see `StationCodeGenerator`).
+//!
+//! Usage: `id_gen [stazioni_coord.tsv]`. The list has a header and the name, ViaggiaTreno ID,
+//! region ID, latitude and longitude of every station. `stations.tsv` and `id_vt.tsv` are
+//! written in the current directory, while the name, code and encoded code of every station
+//! are printed.
+
+use std::collections::HashMap;
+
+use trenitalia::StationCodeGenerator;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("stazioni_coord.tsv"));
+    let contents = std::fs::read_to_string(&path)?;
+    let mut rows: Vec<Vec<&str>> = Vec::new();
+    for (i, line) in contents.lines().enumerate().skip(1) {
+        let row: Vec<&str> = line.split('\t').map(str::trim).collect();
+        if row.len() != 5 {
+            return Err(format!(
+                "{}:{}: expected 5 columns, found {}",
+                path,
+                i + 1,
+                row.len()
+            )
+            .into());
+        }
+        rows.push(row);
+    }
+
+    // A ViaggiaTreno ID may be listed more than once: the longest name is kept
+    let mut order: Vec<&str> = Vec::new();
+    let mut names: HashMap<&str, &str> = HashMap::new();
+    for row in &rows {
+        match names.get(row[1]) {
+            Some(name) if name.len() >= row[0].len() => {}
+            Some(_) => {
+                names.insert(row[1], row[0]);
+            }
+            None => {
+                order.push(row[1]);
+                names.insert(row[1], row[0]);
+            }
+        }
+    }
+
+    let mut generator = StationCodeGenerator::new();
+    let mut codes = HashMap::new();
+    let mut id_vt = Vec::new();
+    for vt_id in order {
+        let code = generator.generate(names[vt_id])?;
+        println!("{}\t{}\t{}", names[vt_id], code, code.encode());
+        codes.insert(vt_id, code);
+        id_vt.push(format!("{}\t{}", code, vt_id));
+    }
+
+    let stations: Vec<String> = rows
+        .iter()
+        .map(|row| [row[0], codes[row[1]].as_str(), row[2], row[3], row[4]].join("\t"))
+        .collect();
+    std::fs::write("stations.tsv", stations.join("\n"))?;
+    std::fs::write("id_vt.tsv", id_vt.join("\n"))?;
+    Ok(())
+}
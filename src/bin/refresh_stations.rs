@@ -0,0 +1,55 @@
+//! Compares the embedded station database with the ViaggiaTreno station lists and writes the
+//! updated TSV files.
+//!
+//! Usage: `refresh_stations [output directory] [moved threshold in km]`. The directory
+//! defaults to the current one and the threshold to 1 km; the changes are printed.
+
+use trenitalia::{StationChange, Trenitalia};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let directory = args.next().unwrap_or_else(|| String::from("."));
+    let threshold_km: f64 = match args.next() {
+        Some(threshold) => threshold.parse()?,
+        None => 1.0,
+    };
+    let trenitalia = Trenitalia::new();
+    let diff = trenitalia.diff_stations(&trenitalia.station_list()?, threshold_km)?;
+    for change in &diff.changes {
+        match change {
+            StationChange::Added(station) => {
+                println!("added\t{}\t{}", station.id, station.get_name())
+            }
+            StationChange::Removed(station) => {
+                println!("removed\t{}\t{}", station.id, station.get_name())
+            }
+            StationChange::Renamed { station, from } => {
+                println!(
+                    "renamed\t{}\t{} -> {}",
+                    station.id,
+                    from,
+                    station.get_name()
+                )
+            }
+            StationChange::RegionChanged { station, from } => println!(
+                "region\t{}\t{} ({} -> {})",
+                station.id,
+                station.get_name(),
+                from,
+                station.region_id
+            ),
+            StationChange::Moved {
+                station,
+                distance_km,
+                ..
+            } => println!(
+                "moved\t{}\t{} ({:.2} km)",
+                station.id,
+                station.get_name(),
+                distance_km
+            ),
+        }
+    }
+    diff.write_files(&directory)?;
+    Ok(())
+}
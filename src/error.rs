@@ -0,0 +1,93 @@
+/// Errors returned by the Trenitalia APIs
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The request could not be sent or the response could not be read
+    Transport(String),
+    /// The server answered with an unsuccessful HTTP status
+    HttpStatus { url: String, status: u16 },
+    /// The response body doesn't have the expected format
+    MalformedPayload(String),
+    /// A station returned by the API (or requested by the caller) is not in the station database
+    UnknownStation(String),
+    /// More than one train has this number and none of them matches the given hint
+    AmbiguousTrain(u32),
+    /// No train with this number is running
+    TrainNotFound(u32),
+    /// A date or time returned by the API could not be parsed
+    TimeParse(String),
+    /// A region code doesn't match any `Region`
+    UnknownRegion(u8),
+    /// A station code is not made of three letters from A to Z, or a station name has no letters
+    /// to build a code from
+    InvalidStationCode(String),
+    /// A station database file could not be read or written, or has invalid contents.
+    /// `line` is the line with the error, if it's about a single line.
+    StationData {
+        file: String,
+        line: Option<usize>,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(message) => write!(f, "transport error: {}", message),
+            Self::HttpStatus { url, status } => write!(f, "HTTP {} from {}", status, url),
+            Self::MalformedPayload(message) => write!(f, "malformed payload: {}", message),
+            Self::UnknownStation(name) => write!(f, "unknown station: {}", name),
+            Self::AmbiguousTrain(number) => write!(f, "ambiguous train number: {}", number),
+            Self::TrainNotFound(number) => write!(f, "train not found: {}", number),
+            Self::TimeParse(value) => write!(f, "invalid date or time: {}", value),
+            Self::UnknownRegion(id) => write!(f, "unknown region: {}", id),
+            Self::InvalidStationCode(code) => write!(f, "invalid station code: {}", code),
+            Self::StationData {
+                file,
+                line: Some(line),
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+            Self::StationData {
+                file,
+                line: None,
+                message,
+            } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ureq::Error> for Error {
+    fn from(error: ureq::Error) -> Self {
+        match error {
+            ureq::Error::Status(status, response) => Self::HttpStatus {
+                url: response.get_url().to_string(),
+                status,
+            },
+            ureq::Error::Transport(transport) => Self::Transport(transport.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Transport(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::MalformedPayload(error.to_string())
+    }
+}
+
+impl From<crate::station_tables::TableError> for Error {
+    fn from(error: crate::station_tables::TableError) -> Self {
+        Self::StationData {
+            file: error.file.to_string(),
+            line: error.line,
+            message: error.message,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Great-circle distances and a grid index for the spatial queries on the stations
+
+use std::collections::HashMap;
+
+use crate::types::TrainStation;
+
+/// Mean radius of the Earth, in kilometres
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+/// Side of a grid cell, in degrees
+const CELL_SIZE: f64 = 0.1;
+
+/// Returns the great-circle distance in kilometres between two (latitude, longitude) points
+pub fn haversine_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.1 - from.1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Length in kilometres of a degree of latitude
+fn km_per_degree() -> f64 {
+    EARTH_RADIUS_KM * std::f64::consts::PI / 180.0
+}
+
+fn cell_of(point: (f64, f64)) -> (i32, i32) {
+    (
+        (point.0 / CELL_SIZE).floor() as i32,
+        (point.1 / CELL_SIZE).floor() as i32,
+    )
+}
+
+/// Stations grouped by the grid cell they fall in
+pub(crate) struct GridIndex {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Smallest and largest cell coordinates that contain a station
+    min_cell: (i32, i32),
+    max_cell: (i32, i32),
+    /// Cosine of the largest absolute latitude, which gives the shortest degree of longitude
+    min_cos_lat: f64,
+}
+
+impl GridIndex {
+    pub(crate) fn new(stations: &[TrainStation]) -> Self {
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        let mut min_cell = (i32::MAX, i32::MAX);
+        let mut max_cell = (i32::MIN, i32::MIN);
+        let mut max_abs_lat: f64 = 0.0;
+        for (i, station) in stations.iter().enumerate() {
+            let cell = cell_of(station.position);
+            cells.entry(cell).or_default().push(i);
+            min_cell = (min_cell.0.min(cell.0), min_cell.1.min(cell.1));
+            max_cell = (max_cell.0.max(cell.0), max_cell.1.max(cell.1));
+            max_abs_lat = max_abs_lat.max(station.position.0.abs() + CELL_SIZE);
+        }
+        GridIndex {
+            cells,
+            min_cell,
+            max_cell,
+            min_cos_lat: max_abs_lat.min(89.0).to_radians().cos(),
+        }
+    }
+
+    /// Calls `visit` with the stations in the cells from `min` to `max`, bounds included
+    fn visit_cells(&self, min: (i32, i32), max: (i32, i32), mut visit: impl FnMut(usize)) {
+        let min = (min.0.max(self.min_cell.0), min.1.max(self.min_cell.1));
+        let max = (max.0.min(self.max_cell.0), max.1.min(self.max_cell.1));
+        for lat in min.0..=max.0 {
+            for lon in min.1..=max.1 {
+                if let Some(cell) = self.cells.get(&(lat, lon)) {
+                    cell.iter().copied().for_each(&mut visit);
+                }
+            }
+        }
+    }
+
+    /// Calls `visit` with the stations in the square ring of cells `ring` cells away from `center`
+    fn visit_ring(&self, center: (i32, i32), ring: i32, mut visit: impl FnMut(usize)) {
+        if ring == 0 {
+            return self.visit_cells(center, center, visit);
+        }
+        let (bottom, top) = (center.0 - ring, center.0 + ring);
+        let (left, right) = (center.1 - ring, center.1 + ring);
+        self.visit_cells((bottom, left), (bottom, right), &mut visit);
+        self.visit_cells((top, left), (top, right), &mut visit);
+        self.visit_cells((bottom + 1, left), (top - 1, left), &mut visit);
+        self.visit_cells((bottom + 1, right), (top - 1, right), &mut visit);
+    }
+
+    /// Returns the `k` stations closest to `point`, with their distance, closest first
+    pub(crate) fn nearest<'a>(
+        &self,
+        stations: &'a [TrainStation],
+        point: (f64, f64),
+        k: usize,
+    ) -> Vec<(&'a TrainStation, f64)> {
+        let center = cell_of(point);
+        let mut found: Vec<(&TrainStation, f64)> = Vec::new();
+        if k == 0 || self.cells.is_empty() {
+            return found;
+        }
+        // Grow a square of cells around the point, one ring at a time, until the stations
+        // outside the square can't be closer than the k-th station found
+        let max_ring = [
+            center.0 - self.min_cell.0,
+            self.max_cell.0 - center.0,
+            center.1 - self.min_cell.1,
+            self.max_cell.1 - center.1,
+        ]
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(0);
+        let min_cos_lat = self
+            .min_cos_lat
+            .min((point.0.abs() + CELL_SIZE).min(89.0).to_radians().cos());
+        let min_cell_km = CELL_SIZE * km_per_degree() * min_cos_lat;
+        for ring in 0..=max_ring {
+            self.visit_ring(center, ring, |i| {
+                let station = &stations[i];
+                found.push((station, haversine_km(point, station.position)));
+            });
+            if found.len() >= k {
+                sort_by_distance(&mut found);
+                found.truncate(k);
+                if found[k - 1].1 <= ring as f64 * min_cell_km {
+                    break;
+                }
+            }
+        }
+        sort_by_distance(&mut found);
+        found
+    }
+
+    /// Returns the stations within `radius_km` from `point`, with their distance, closest first
+    pub(crate) fn within<'a>(
+        &self,
+        stations: &'a [TrainStation],
+        point: (f64, f64),
+        radius_km: f64,
+    ) -> Vec<(&'a TrainStation, f64)> {
+        let d_lat = radius_km / km_per_degree();
+        let max_abs_lat = (point.0.abs() + d_lat).min(89.0);
+        let d_lon = (radius_km / (km_per_degree() * max_abs_lat.to_radians().cos())).min(180.0);
+        let mut found: Vec<(&TrainStation, f64)> = Vec::new();
+        self.visit_cells(
+            cell_of((point.0 - d_lat, point.1 - d_lon)),
+            cell_of((point.0 + d_lat, point.1 + d_lon)),
+            |i| {
+                let distance = haversine_km(point, stations[i].position);
+                if distance <= radius_km {
+                    found.push((&stations[i], distance));
+                }
+            },
+        );
+        sort_by_distance(&mut found);
+        found
+    }
+
+    /// Returns the stations between the `south_west` and `north_east` corners, with their
+    /// distance from the centre of the box, closest first
+    pub(crate) fn in_box<'a>(
+        &self,
+        stations: &'a [TrainStation],
+        south_west: (f64, f64),
+        north_east: (f64, f64),
+    ) -> Vec<(&'a TrainStation, f64)> {
+        let center = (
+            (south_west.0 + north_east.0) / 2.0,
+            (south_west.1 + north_east.1) / 2.0,
+        );
+        let mut found: Vec<(&TrainStation, f64)> = Vec::new();
+        self.visit_cells(cell_of(south_west), cell_of(north_east), |i| {
+            let position = stations[i].position;
+            if (south_west.0..=north_east.0).contains(&position.0)
+                && (south_west.1..=north_east.1).contains(&position.1)
+            {
+                found.push((&stations[i], haversine_km(center, position)));
+            }
+        });
+        sort_by_distance(&mut found);
+        found
+    }
+}
+
+fn sort_by_distance(stations: &mut [(&TrainStation, f64)]) {
+    stations.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.id.cmp(&b.0.id)));
+}
@@ -0,0 +1,372 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::types::{TrainInfo, TrainNumber, TrainStation, TrainTrip};
+use crate::{Error, Trenitalia};
+
+/// A journey between two stations, made of one or more trains
+///
+/// With the `serde` feature it's represented as `{"legs": [...], "price": 12.5}`.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "RawJourney")
+)]
+pub struct Journey {
+    legs: Vec<TrainTrip>,
+    price: Option<f64>,
+}
+
+/// A deserialized journey, before the check that it has at least one leg
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawJourney {
+    legs: Vec<TrainTrip>,
+    price: Option<f64>,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<RawJourney> for Journey {
+    type Error = &'static str;
+
+    fn try_from(raw: RawJourney) -> Result<Self, Self::Error> {
+        Journey::new(raw.legs, raw.price).ok_or("a journey needs at least one leg")
+    }
+}
+
+/// A change of train between two consecutive legs of a journey
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Transfer {
+    /// Station and time of arrival of the previous train
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::rome_datetime::station")
+    )]
+    pub arrival: (TrainStation, chrono::DateTime<chrono_tz::Tz>),
+    /// Station and time of departure of the next train
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::rome_datetime::station")
+    )]
+    pub departure: (TrainStation, chrono::DateTime<chrono_tz::Tz>),
+}
+
+impl Transfer {
+    /// This method returns the time available to change train
+    pub fn get_wait(&self) -> chrono::Duration {
+        self.departure.1.signed_duration_since(self.arrival.1)
+    }
+    /// Whether the next train leaves from a different station
+    pub fn changes_station(&self) -> bool {
+        self.arrival.0.id != self.departure.0.id
+    }
+}
+
+impl Journey {
+    /// Creates a journey from its legs, in travel order. Returns `None` if there are no legs
+    pub fn new(legs: Vec<TrainTrip>, price: Option<f64>) -> Option<Self> {
+        if legs.is_empty() {
+            None
+        } else {
+            Some(Journey { legs, price })
+        }
+    }
+    /// The trains to take, in travel order
+    pub fn legs(&self) -> &[TrainTrip] {
+        &self.legs
+    }
+    /// The price of the journey, when it's known (only LeFrecce solutions have one)
+    pub fn price(&self) -> Option<f64> {
+        self.price
+    }
+    /// The station the journey starts from
+    pub fn origin(&self) -> &TrainStation {
+        &self.legs[0].departure.0
+    }
+    /// The station the journey ends in
+    pub fn destination(&self) -> &TrainStation {
+        &self.legs[self.legs.len() - 1].arrival.0
+    }
+    /// Departure time of the first train
+    pub fn departure_time(&self) -> chrono::DateTime<chrono_tz::Tz> {
+        self.legs[0].departure.1
+    }
+    /// Arrival time of the last train
+    pub fn arrival_time(&self) -> chrono::DateTime<chrono_tz::Tz> {
+        self.legs[self.legs.len() - 1].arrival.1
+    }
+    /// This method returns the total duration of the journey, transfers included
+    pub fn get_duration(&self) -> chrono::Duration {
+        self.arrival_time()
+            .signed_duration_since(self.departure_time())
+    }
+    /// Number of changes of train
+    pub fn changes(&self) -> usize {
+        self.legs.len() - 1
+    }
+    /// The changes of train, in travel order
+    pub fn transfers(&self) -> Vec<Transfer> {
+        self.legs
+            .windows(2)
+            .map(|legs| Transfer {
+                arrival: legs[0].arrival.clone(),
+                departure: legs[1].departure.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Whether a change of train can still be made
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConnectionStatus {
+    /// There's at least the minimum time to change train
+    Safe,
+    /// There's less than the minimum time to change train
+    AtRisk,
+    /// The next train leaves before the previous one arrives, or one of the stops is cancelled
+    Missed,
+}
+
+/// Live assessment of a change of train
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConnectionRisk {
+    /// The change of train, with its scheduled times
+    pub transfer: Transfer,
+    /// When the previous train is expected to arrive, delay included
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime"))]
+    pub expected_arrival: chrono::DateTime<chrono_tz::Tz>,
+    /// When the next train is expected to leave, delay included
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime"))]
+    pub expected_departure: chrono::DateTime<chrono_tz::Tz>,
+    /// Time left to change train, negative if the connection is missed
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::duration_seconds"))]
+    pub buffer: chrono::Duration,
+    pub status: ConnectionStatus,
+    /// The first later journey to the destination, if the connection isn't safe
+    pub fallback: Option<Journey>,
+}
+
+/// Live status of a train at one of its stops
+struct LiveStop {
+    time: chrono::DateTime<chrono_tz::Tz>,
+    /// Whether the train has already arrived at or left the stop
+    done: bool,
+    cancelled: bool,
+}
+
+impl LiveStop {
+    /// Estimates the arrival (or departure) of a train at a stop, using its scheduled time
+    /// when there's no live data
+    fn new(
+        info: Option<&TrainInfo>,
+        station: &TrainStation,
+        scheduled: chrono::DateTime<chrono_tz::Tz>,
+        arrival: bool,
+    ) -> Self {
+        let info = match info {
+            Some(info) => info,
+            None => {
+                return LiveStop {
+                    time: scheduled,
+                    done: false,
+                    cancelled: false,
+                }
+            }
+        };
+        // Trains may arrive early, but they don't leave early
+        let delay = if arrival {
+            info.current_delay
+        } else {
+            info.current_delay.max(0)
+        };
+        let estimate = scheduled + chrono::Duration::minutes(delay as i64);
+        match info.stops.iter().find(|stop| stop.station.id == station.id) {
+            Some(stop) => {
+                let actual = if arrival {
+                    stop.arrival
+                } else {
+                    stop.departure
+                };
+                LiveStop {
+                    time: actual.unwrap_or(estimate),
+                    done: actual.is_some(),
+                    cancelled: stop.is_cancelled(),
+                }
+            }
+            None => LiveStop {
+                time: estimate,
+                done: false,
+                cancelled: false,
+            },
+        }
+    }
+}
+
+/// Fetches the live status of a leg, if ViaggiaTreno knows the train. Buses aren't looked up.
+fn live_info(trenitalia: &Trenitalia, leg: &TrainTrip) -> Result<Option<TrainInfo>, Error> {
+    if let TrainNumber::Bus { .. } = leg.train_number {
+        return Ok(None);
+    }
+    optional_live_info(
+        trenitalia.train_info_calling_at(u32::from(&leg.train_number), &leg.departure.0),
+    )
+}
+
+/// Turns the errors that mean there's no live status for a leg (the train is unknown, or
+/// its number doesn't identify a single train) into `None`
+pub(crate) fn optional_live_info(
+    result: Result<TrainInfo, Error>,
+) -> Result<Option<TrainInfo>, Error> {
+    match result {
+        Ok(info) => Ok(Some(info)),
+        Err(Error::TrainNotFound(_)) | Err(Error::AmbiguousTrain(_)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+impl Journey {
+    /// Checks every change of train against the live status of the trains.
+    ///
+    /// A change is at risk when there's less than `min_buffer` to make it. Buses, and trains
+    /// that ViaggiaTreno doesn't know or can't tell apart, are assumed to be on time. For
+    /// changes that aren't safe the first later journey from the station of the change is
+    /// searched as a fallback.
+    pub fn connection_risks(
+        &self,
+        trenitalia: &Trenitalia,
+        min_buffer: chrono::Duration,
+    ) -> Result<Vec<ConnectionRisk>, Error> {
+        if self.legs.len() < 2 {
+            return Ok(vec![]);
+        }
+        let infos = self.live_infos(trenitalia)?;
+        let mut risks = self.assess_connections(&infos, min_buffer);
+        for risk in risks
+            .iter_mut()
+            .filter(|risk| risk.status != ConnectionStatus::Safe)
+        {
+            let journeys = trenitalia.find_trips(
+                &risk.transfer.arrival.0,
+                self.destination(),
+                &risk.expected_arrival,
+            )?;
+            risk.fallback = risk.pick_fallback(journeys);
+        }
+        Ok(risks)
+    }
+
+    /// Fetches the live status of every leg
+    fn live_infos(&self, trenitalia: &Trenitalia) -> Result<Vec<Option<TrainInfo>>, Error> {
+        self.legs
+            .iter()
+            .map(|leg| live_info(trenitalia, leg))
+            .collect()
+    }
+
+    /// Compares the expected times of consecutive legs, given their live status
+    pub(crate) fn assess_connections(
+        &self,
+        infos: &[Option<TrainInfo>],
+        min_buffer: chrono::Duration,
+    ) -> Vec<ConnectionRisk> {
+        self.transfers()
+            .into_iter()
+            .enumerate()
+            .map(|(i, transfer)| {
+                let arrival = LiveStop::new(
+                    infos[i].as_ref(),
+                    &transfer.arrival.0,
+                    transfer.arrival.1,
+                    true,
+                );
+                let departure = LiveStop::new(
+                    infos[i + 1].as_ref(),
+                    &transfer.departure.0,
+                    transfer.departure.1,
+                    false,
+                );
+                let buffer = departure.time.signed_duration_since(arrival.time);
+                let status = if arrival.cancelled
+                    || departure.cancelled
+                    || buffer < chrono::Duration::zero()
+                    || (departure.done && !arrival.done)
+                {
+                    ConnectionStatus::Missed
+                } else if buffer < min_buffer {
+                    ConnectionStatus::AtRisk
+                } else {
+                    ConnectionStatus::Safe
+                };
+                ConnectionRisk {
+                    transfer,
+                    expected_arrival: arrival.time,
+                    expected_departure: departure.time,
+                    buffer,
+                    status,
+                    fallback: None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Journey {
+    /// Searches new ways to reach the destination from where the traveller will be.
+    ///
+    /// The continuations leave from the station of the first change that has been missed, or
+    /// whose inbound train isn't expected to have arrived by `now`, after the expected arrival
+    /// there. If every change up to `now` has been made, the traveller is on the last train
+    /// and there's nothing to replan.
+    pub fn replan(
+        &self,
+        trenitalia: &Trenitalia,
+        now: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<Journey>, Error> {
+        let infos = self.live_infos(trenitalia)?;
+        match self.replan_position(&infos, now) {
+            Some((station, expected_arrival)) => Ok(Self::continuations(
+                trenitalia.find_trips(&station, self.destination(), &expected_arrival)?,
+                &expected_arrival,
+            )),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the station of the first change that has been missed or is still ahead, with
+    /// the expected arrival time there
+    pub(crate) fn replan_position(
+        &self,
+        infos: &[Option<TrainInfo>],
+        now: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Option<(TrainStation, chrono::DateTime<chrono_tz::Tz>)> {
+        self.assess_connections(infos, chrono::Duration::zero())
+            .into_iter()
+            .find(|risk| risk.status == ConnectionStatus::Missed || risk.expected_arrival > *now)
+            .map(|risk| (risk.transfer.arrival.0, risk.expected_arrival))
+    }
+
+    /// Keeps the journeys that leave after the traveller has arrived
+    pub(crate) fn continuations(
+        journeys: Vec<Journey>,
+        after: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Vec<Journey> {
+        journeys
+            .into_iter()
+            .filter(|journey| journey.departure_time() >= *after)
+            .collect()
+    }
+}
+
+impl ConnectionRisk {
+    /// Returns the first journey leaving after the planned next train and after the expected arrival
+    pub(crate) fn pick_fallback(&self, journeys: Vec<Journey>) -> Option<Journey> {
+        journeys.into_iter().find(|journey| {
+            journey.departure_time() > self.transfer.departure.1
+                && journey.departure_time() >= self.expected_arrival
+        })
+    }
+}
@@ -1,7 +1,9 @@
-use chrono::TimeZone;
+#![allow(non_snake_case)]
 use serde::{Deserialize, Serialize};
 
 use crate::types::{TrainStation, TrainTrip};
+use crate::Journey;
+use crate::{Error, Trenitalia};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LFTrain {
@@ -47,6 +49,7 @@ pub struct LFLeg {
     pub arrivalstation: String,
     pub arrivaltime: String,
 }
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LFCredential {
     pub credentialid: u16,
@@ -121,98 +124,100 @@ pub struct LFDetailedSolution {
     pub extraInfo: Vec<String>,
 }
 
-pub fn find_trips(
-    from: &TrainStation,
-    to: &TrainStation,
-    when: &chrono::DateTime<chrono::Local>,
-) -> Vec<Vec<TrainTrip>> {
-    if from.id == to.id || from.lefrecce_name.is_none() || from.lefrecce_name.is_none() {
-        return vec![];
-    }
-    let mut result: Vec<Vec<TrainTrip>> = Vec::new();
-    let client = ureq::agent();
-    let url = format!("https://www.lefrecce.it/msite/api/solutions?origin={}&destination={}&arflag=A&adate={}&atime={}&adultno=1&childno=0&direction=A&frecce=false&onlyRegional=false",
-        from.lefrecce_name.clone().unwrap().replace(" ", "%20"),
-        to.lefrecce_name.clone().unwrap().replace(" ", "%20"),
+/// Builds the URL of the solutions search between two stations, given their LeFrecce names
+pub fn solutions_url(
+    trenitalia: &Trenitalia,
+    from_name: &str,
+    to_name: &str,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> String {
+    // The API reads the date and hour as Italian time
+    let when = when.with_timezone(&crate::TIMEZONE);
+    trenitalia.lefrecce_url(&format!("solutions?origin={}&destination={}&arflag=A&adate={}&atime={}&adultno=1&childno=0&direction=A&frecce=false&onlyRegional=false",
+        from_name.replace(' ', "%20"),
+        to_name.replace(' ', "%20"),
         when.format("%d/%m/%Y"),
         when.format("%H")
-    );
-    if cfg!(debug_assertions) {
-        println!("{}", url);
+    ))
+}
+
+/// Searches the solutions between two stations, given their LeFrecce names
+pub fn search_solutions(
+    trenitalia: &Trenitalia,
+    from_name: &str,
+    to_name: &str,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> Result<Vec<LFSolution>, Error> {
+    let url = solutions_url(trenitalia, from_name, to_name, when);
+    Ok(serde_json::from_str(&trenitalia.http_get(&url)?)?)
+}
+
+/// Builds the URL of the details of a solution
+pub fn details_url(trenitalia: &Trenitalia, solution: &LFSolution) -> String {
+    trenitalia.lefrecce_url(&format!("solutions/{}/standardoffers", solution.idsolution))
+}
+
+/// Returns the LeFrecce names of two stations, or `None` if LeFrecce can't be searched between them
+pub fn search_names<'a>(
+    from: &'a TrainStation,
+    to: &'a TrainStation,
+) -> Option<(&'a str, &'a str)> {
+    match (&from.lefrecce_name, &to.lefrecce_name) {
+        (Some(from_name), Some(to_name)) if from.id != to.id => Some((from_name, to_name)),
+        _ => None,
     }
-    let body: Vec<LFSolution> = serde_json::from_value(
-        client
-            .get(url.as_str())
-            .call()
-            .unwrap()
-            .into_json()
-            .unwrap(),
-    )
-    .unwrap();
+}
+
+pub fn find_trips(
+    trenitalia: &Trenitalia,
+    from: &TrainStation,
+    to: &TrainStation,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> Result<Vec<Journey>, Error> {
+    let (from_name, to_name) = match search_names(from, to) {
+        Some(names) => names,
+        None => return Ok(vec![]),
+    };
+    let mut result: Vec<Journey> = Vec::new();
+    let body = search_solutions(trenitalia, from_name, to_name, when)?;
     for solution in &body {
-        let mut train_trips: Vec<TrainTrip> = Vec::new();
-        let url_details = format!(
-            "https://www.lefrecce.it/msite/api/solutions/{}/standardoffers",
-            solution.idsolution
-        );
-        if cfg!(debug_assertions) {
-            println!("{}", url_details);
-        }
-        let body_details: LFDetailedSolution = serde_json::from_value(
-            client
-                .get(url_details.as_str())
-                .call()
-                .expect("Failed API call")
-                .into_json()
-                .unwrap(),
-        )
-        .unwrap();
-        for leg in &body_details.leglist {
-            for train in &leg.segments {
-                if train.trainidentifier == String::from("Same") {
-                    continue;
-                }
-                let acronym = train
-                    .trainacronym
-                    .as_ref()
-                    .map_or(String::from(""), |x| String::from(x.as_str()));
-                let train_name_exploded: Vec<&str> = train.trainidentifier.split(' ').collect();
-                let train_number = train_name_exploded[&train_name_exploded.len() - 1];
-                unimplemented!();
-                let from = from;
-                let to = to;
-                train_trips.push(TrainTrip {
-                    departure: (
-                        from.clone(),
-                        chrono::Local
-                            .datetime_from_str(train.departuretime.as_str(), "%+")
-                            .expect("Data non valida"),
-                    ),
-                    arrival: (
-                        to.clone(),
-                        chrono::Local
-                            .datetime_from_str(train.arrivaltime.as_str(), "%+")
-                            .expect("Data non valida"),
-                    ),
-                    train_number: crate::utils::match_train_type(
-                        &acronym,
-                        train_number.parse::<u32>().unwrap_or(
-                            train_number
-                                .chars()
-                                .into_iter()
-                                .map(|x| if x.is_digit(10) { x } else { '0' })
-                                .collect::<String>()
-                                .parse::<u32>()
-                                .unwrap(),
-                        ),
-                    ),
-                });
+        let body_details: LFDetailedSolution =
+            serde_json::from_str(&trenitalia.http_get(&details_url(trenitalia, solution))?)?;
+        result.extend(Journey::new(
+            trips_from_details(trenitalia, &body_details)?,
+            solution.minprice,
+        ));
+    }
+    Ok(result)
+}
+
+/// Converts the details of a solution to a list of trains
+pub fn trips_from_details(
+    trenitalia: &Trenitalia,
+    details: &LFDetailedSolution,
+) -> Result<Vec<TrainTrip>, Error> {
+    let mut train_trips: Vec<TrainTrip> = Vec::new();
+    for leg in &details.leglist {
+        for train in &leg.segments {
+            if train.trainidentifier == "Same" {
+                continue;
             }
+            let acronym = train.trainacronym.as_deref().unwrap_or("");
+            let train_number = train.trainidentifier.split(' ').next_back().unwrap_or("");
+            let from = trenitalia.station_from_lefrecce_name(&train.departurestation);
+            let to = trenitalia.station_from_lefrecce_name(&train.arrivalstation);
+            train_trips.push(TrainTrip {
+                departure: (
+                    from,
+                    crate::utils::parse_offset_datetime(&train.departuretime)?,
+                ),
+                arrival: (to, crate::utils::parse_offset_datetime(&train.arrivaltime)?),
+                train_number: crate::utils::match_train_type(
+                    acronym,
+                    crate::utils::parse_train_number(train_number)?,
+                ),
+            });
         }
-        result.push(train_trips);
     }
-    /*if cfg!(debug_assertions) {
-        println!("{:?}", result);
-    }*/
-    result
+    Ok(train_trips)
 }
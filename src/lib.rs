@@ -1,488 +1,497 @@
-use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
-use types::*;
+#[cfg(feature = "async")]
+pub use asynchronous::{AsyncTransport, BlockingTransport, TransportFuture};
+pub use chrono_tz;
+pub use error::Error;
+pub use geo::haversine_km;
+pub use journey::{ConnectionRisk, ConnectionStatus, Journey, Transfer};
+pub use normalize::normalize;
+pub use refresh::{RemoteStation, StationChange, StationDiff};
+pub use station_code::{StationCode, StationCodeGenerator};
+pub use transport::{FixtureTransport, Transport, UreqTransport};
+pub use types::*;
+pub use watcher::{Clock, SystemClock, TrainEvent, TrainWatcher, WatchedTrain};
 
-mod mapping;
-#[macro_use]
-mod utils;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod autocomplete;
+mod error;
+mod geo;
+mod journey;
 mod lefrecce;
+mod mapping;
+mod normalize;
+mod refresh;
+mod station_code;
+mod station_tables;
+mod stations;
+mod transport;
 mod types;
+mod utils;
 mod viaggiatreno;
+mod watcher;
 
 #[cfg(test)]
 mod tests;
 
-const WORDS_EQUALITY_THRESHOLD: f64 = 0.70;
+/// Time zone of every date and time returned by the crate
+pub const TIMEZONE: chrono_tz::Tz = chrono_tz::Europe::Rome;
 
-//pub struct TrainTrips(Vec<TrainTrip>);
+const WORDS_EQUALITY_THRESHOLD: f64 = 0.70;
+const VIAGGIATRENO_BASE_URL: &str = "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno";
+const LEFRECCE_BASE_URL: &str = "https://www.lefrecce.it/msite/api";
 
-/*impl TrainTrips{
-    /// This method calculates the total duration of a trip
-    pub fn get_duration(&self) -> chrono::Duration {
-        let partenza = (&self.0[0].departure.1).clone();
-        let arrivo = (&self.0[&self.0.len() - 1].arrival.1).clone();
-        arrivo.signed_duration_since(partenza)
-    }
-}*/
 // TODO Aggiungere tipi treno
 
 pub struct Trenitalia {
-    stations: Vec<TrainStation>,
-    /// Hash map that matches aliases to indexes of the `stations` vector
-    fast_station_lookup: std::collections::HashMap<String, usize>,
+    stations: stations::StationDatabase,
+    /// HTTP client used for every API call
+    transport: std::sync::Arc<dyn Transport>,
+    /// Base URL of the ViaggiaTreno REST API, without the trailing slash
+    viaggiatreno_base_url: String,
+    /// Base URL of the LeFrecce API, without the trailing slash
+    lefrecce_base_url: String,
+    /// HTTP client used for every asynchronous API call
+    #[cfg(feature = "async")]
+    async_transport: std::sync::Arc<dyn AsyncTransport>,
+}
+
+/// Builder for a `Trenitalia` instance with a custom transport, custom API endpoints or
+/// a custom station database
+pub struct TrenitaliaBuilder {
+    /// Station database, the embedded one when `None`
+    stations: Option<stations::StationDatabase>,
+    transport: std::sync::Arc<dyn Transport>,
+    viaggiatreno_base_url: String,
+    lefrecce_base_url: String,
+    #[cfg(feature = "async")]
+    async_transport: Option<std::sync::Arc<dyn AsyncTransport>>,
+}
+
+impl Default for TrenitaliaBuilder {
+    fn default() -> Self {
+        TrenitaliaBuilder {
+            stations: None,
+            transport: std::sync::Arc::new(UreqTransport::new()),
+            viaggiatreno_base_url: VIAGGIATRENO_BASE_URL.to_string(),
+            lefrecce_base_url: LEFRECCE_BASE_URL.to_string(),
+            #[cfg(feature = "async")]
+            async_transport: None,
+        }
+    }
+}
+
+impl TrenitaliaBuilder {
+    /// Sets the HTTP client used for every API call
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = std::sync::Arc::new(transport);
+        self
+    }
+
+    /// Sets the HTTP client used for every asynchronous API call.
+    /// By default the blocking transport is run on the tokio blocking thread pool.
+    #[cfg(feature = "async")]
+    pub fn async_transport(mut self, transport: impl AsyncTransport + 'static) -> Self {
+        self.async_transport = Some(std::sync::Arc::new(transport));
+        self
+    }
+
+    /// Sets the base URL of the ViaggiaTreno API
+    /// (default: `http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno`)
+    pub fn viaggiatreno_base_url(mut self, url: &str) -> Self {
+        self.viaggiatreno_base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the base URL of the LeFrecce API (default: `https://www.lefrecce.it/msite/api`)
+    pub fn lefrecce_base_url(mut self, url: &str) -> Self {
+        self.lefrecce_base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Loads the station database from four TSV files, in the same format as the embedded ones:
+    /// `stations.tsv` (name, ID, region ID, latitude, longitude), `aliases.tsv` (alias, ID),
+    /// `id_lf_map.tsv` (ID, LeFrecce name) and `id_vt.tsv` (ID, ViaggiaTreno ID).
+    /// By default the embedded station database is used.
+    pub fn stations_from_files(
+        mut self,
+        stations: impl AsRef<std::path::Path>,
+        aliases: impl AsRef<std::path::Path>,
+        lefrecce_names: impl AsRef<std::path::Path>,
+        vt_ids: impl AsRef<std::path::Path>,
+    ) -> Result<Self, Error> {
+        self.stations = Some(stations::StationDatabase::from_files(
+            stations.as_ref(),
+            aliases.as_ref(),
+            lefrecce_names.as_ref(),
+            vt_ids.as_ref(),
+        )?);
+        Ok(self)
+    }
+
+    /// Loads the station database from four readers, see `stations_from_files`
+    pub fn stations_from_readers(
+        mut self,
+        stations: impl std::io::Read,
+        aliases: impl std::io::Read,
+        lefrecce_names: impl std::io::Read,
+        vt_ids: impl std::io::Read,
+    ) -> Result<Self, Error> {
+        self.stations = Some(stations::StationDatabase::from_readers(
+            stations,
+            aliases,
+            lefrecce_names,
+            vt_ids,
+        )?);
+        Ok(self)
+    }
+
+    /// Creates the Trenitalia instance
+    pub fn build(self) -> Trenitalia {
+        let stations = self
+            .stations
+            .unwrap_or_else(stations::StationDatabase::embedded);
+        #[cfg(feature = "async")]
+        let transport = self.transport.clone();
+        Trenitalia {
+            stations,
+            #[cfg(feature = "async")]
+            async_transport: self
+                .async_transport
+                .unwrap_or_else(|| std::sync::Arc::new(BlockingTransport::from_arc(transport))),
+            transport: self.transport,
+            viaggiatreno_base_url: self.viaggiatreno_base_url,
+            lefrecce_base_url: self.lefrecce_base_url,
+        }
+    }
+}
+
+impl Default for Trenitalia {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Trenitalia {
     /// Creates a new Trenitalia instance
     pub fn new() -> Trenitalia {
-        let id_to_lf_tsv = include_str!("../id_lf_map.tsv");
-        let id_to_lf: std::collections::HashMap<String, String> = id_to_lf_tsv
-            .split("\n")
-            .collect::<Vec<&str>>()
-            .iter()
-            .map(|&x| x.split("\t").collect::<Vec<&str>>())
-            .collect::<Vec<Vec<&str>>>()
-            .iter()
-            .map(|x| (String::from(*&x[0]), String::from(*&x[1])))
-            .collect::<Vec<(String, String)>>()
-            .into_iter()
-            .collect();
-        let id_to_vt_tsv = include_str!("../id_vt.tsv");
-        let id_to_vt: std::collections::HashMap<String, String> = id_to_vt_tsv
-            .split("\n")
-            .collect::<Vec<&str>>()
-            .iter()
-            .map(|&x| x.split("\t").collect::<Vec<&str>>())
-            .collect::<Vec<Vec<&str>>>()
-            .iter()
-            .map(|x| (String::from(*&x[0]), String::from(*&x[1])))
-            .collect::<Vec<(String, String)>>()
-            .into_iter()
-            .collect();
+        Self::builder().build()
+    }
 
-        let aliases_tsv = include_str!("../aliases.tsv");
-        let aliases: Vec<Vec<&str>> = aliases_tsv
-            .split("\n")
-            .collect::<Vec<&str>>()
-            .iter()
-            .map(|&x| x.split("\t").collect::<Vec<&str>>())
-            .collect::<Vec<Vec<&str>>>();
+    /// Creates a new Trenitalia instance that performs its API calls through `transport`
+    pub fn with_transport(transport: impl Transport + 'static) -> Trenitalia {
+        Self::builder().transport(transport).build()
+    }
 
-        let station_list_tsv = include_str!("../stations.tsv");
-        let station_list = station_list_tsv.split("\n").collect::<Vec<&str>>();
-        let mapped_stations: Vec<TrainStation> = station_list
-            .iter()
-            .map(|&x| x.split("\t").collect::<Vec<&str>>())
-            .collect::<Vec<Vec<&str>>>()
-            .iter()
-            .map(|x| {
-                let mut a = vec![String::from(x[0])];
-                let mut v: Vec<String> = Vec::new();
-                for alias in &aliases {
-                    if &alias[1] == &x[1] {
-                        v.push(String::from(alias[0]))
-                    }
-                }
-                a.append(&mut v);
-                TrainStation {
-                    id: String::from(x[1]),
-                    aliases: a,
-                    position: (x[3].parse::<f64>().unwrap(), x[4].parse::<f64>().unwrap()),
-                    region_id: x[2].parse::<u8>().unwrap(),
-                    lefrecce_name: id_to_lf.get(x[1]).map(|x| String::from(x)),
-                    vt_id: id_to_vt.get(x[1]).map(|x| String::from(x)),
-                }
+    /// Creates a new Trenitalia instance with the station database loaded from four TSV files,
+    /// see `TrenitaliaBuilder::stations_from_files`
+    pub fn from_files(
+        stations: impl AsRef<std::path::Path>,
+        aliases: impl AsRef<std::path::Path>,
+        lefrecce_names: impl AsRef<std::path::Path>,
+        vt_ids: impl AsRef<std::path::Path>,
+    ) -> Result<Trenitalia, Error> {
+        Ok(Self::builder()
+            .stations_from_files(stations, aliases, lefrecce_names, vt_ids)?
+            .build())
+    }
+
+    /// Creates a new Trenitalia instance with the station database read from four readers,
+    /// see `TrenitaliaBuilder::stations_from_files`
+    pub fn from_readers(
+        stations: impl std::io::Read,
+        aliases: impl std::io::Read,
+        lefrecce_names: impl std::io::Read,
+        vt_ids: impl std::io::Read,
+    ) -> Result<Trenitalia, Error> {
+        Ok(Self::builder()
+            .stations_from_readers(stations, aliases, lefrecce_names, vt_ids)?
+            .build())
+    }
+
+    /// Returns a builder to customise the transport, the API endpoints and the station database
+    pub fn builder() -> TrenitaliaBuilder {
+        TrenitaliaBuilder::default()
+    }
+
+    /// Builds the URL of a ViaggiaTreno API method
+    pub(crate) fn viaggiatreno_url(&self, path: &str) -> String {
+        format!("{}/{}", self.viaggiatreno_base_url, path)
+    }
+
+    /// Builds the URL of a LeFrecce API method
+    pub(crate) fn lefrecce_url(&self, path: &str) -> String {
+        format!("{}/{}", self.lefrecce_base_url, path)
+    }
+
+    /// Performs a GET request through the configured transport
+    pub(crate) fn http_get(&self, url: &str) -> Result<String, Error> {
+        self.transport.get(url)
+    }
+
+    /// Return the station indexed under a name returned by one of the APIs
+    pub(crate) fn station_from_api_name(
+        &self,
+        name: Option<&String>,
+    ) -> Result<&TrainStation, Error> {
+        let name = name.map(String::as_str).unwrap_or("");
+        self.stations
+            .by_name(name)
+            .ok_or_else(|| Error::UnknownStation(name.to_string()))
+    }
+
+    /// Return the station with a name returned by the LeFrecce API, or an unlisted station
+    /// (e.g. one abroad) when the name isn't in the station database
+    pub(crate) fn station_from_lefrecce_name(&self, name: &str) -> TrainStation {
+        self.stations
+            .by_name(name)
+            .cloned()
+            .unwrap_or_else(|| TrainStation {
+                lefrecce_name: Some(name.to_string()),
+                ..TrainStation::unlisted(name, None)
             })
-            .collect();
-        let mut lookup: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-        for i in 0..mapped_stations.len() {
-            for alias in &mapped_stations[i].aliases {
-                lookup.insert(String::from(alias), i);
-            }
-            if mapped_stations[i].lefrecce_name.is_some() {
-                lookup.insert(
-                    String::from(
-                        mapped_stations[i]
-                            .lefrecce_name
-                            .as_ref()
-                            .unwrap_or(&"".to_string()),
-                    )
-                    .to_uppercase(),
-                    i,
-                );
-            }
-        }
-        Trenitalia {
-            stations: mapped_stations,
-            fast_station_lookup: lookup,
-        }
     }
-    /// Find a trip between two stations using LeFrecce API
+
     /// Find a trip between two stations using ViaggiaTreno API and falling back to LeFrecce
     pub fn find_trips(
         &self,
         from: &TrainStation,
         to: &TrainStation,
-        when: &chrono::DateTime<chrono::Local>,
-    ) -> Vec<Vec<TrainTrip>> {
-        let mut result: Vec<Vec<TrainTrip>> = Vec::new();
-        let url = format!("http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/soluzioniViaggioNew/{}/{}/{}",
-            from.short_id().unwrap(),
-            to.short_id().unwrap(),
-            when.format("%FT%T")
-        );
-        if cfg!(debug_assertions) {
-            println!("{}", url);
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<Journey>, Error> {
+        let mut result: Vec<Journey> = Vec::new();
+        let url = viaggiatreno::solutions_url(self, from, to, when)?;
+        let body: mapping::VTJourneySearchResult = serde_json::from_str(&self.http_get(&url)?)?;
+        if body.soluzioni.is_empty() {
+            return lefrecce::find_trips(self, from, to, when);
         }
-        let body: mapping::VTJourneySearchResult = serde_json::from_value(
-            ureq::get(url.as_str())
-                .call()
-                .expect("Failed API call")
-                .into_json()
-                .unwrap(),
-        )
-        .unwrap();
-        if body.soluzioni.len() == 0 {
-            return lefrecce::find_trips(from, to, when);
-        }
-        for soluzione in body.soluzioni {
+        for soluzione in &body.soluzioni {
             let mut train_trips: Vec<TrainTrip> = Vec::new();
-            if cfg!(debug_assertions) {
-                println!(
-                    "expected: {}, found: {}, delta: {}",
-                    &from.get_name(),
-                    &soluzione.vehicles[0]
-                        .origine
-                        .as_ref()
-                        .unwrap_or(&String::from("")),
-                    utils::match_strings(
-                        &soluzione.vehicles[0]
-                            .origine
-                            .as_ref()
-                            .unwrap_or(&String::from(""))
-                            .to_lowercase(),
-                        &from.get_name()
-                    )
-                );
-            }
-            if utils::match_strings(
-                &soluzione.vehicles[0]
-                    .origine
-                    .as_ref()
-                    .unwrap_or(&String::from("")),
-                &from.get_name(),
-            ) < WORDS_EQUALITY_THRESHOLD
-            {
-                let filling_to = &self.stations[*self
-                    .fast_station_lookup
-                    .get(
-                        soluzione.vehicles[0]
-                            .origine
-                            .as_ref()
-                            .unwrap_or(&String::from("")),
-                    )
-                    .expect("Inconsistency in Trenitalia")];
-                if cfg!(debug_assertions) {
-                    println!("filling_to = {:?}", filling_to);
-                }
-                let filling_solutions = lefrecce::find_trips(from, filling_to, when);
-                for filling_solution in filling_solutions.iter() {
-                    if filling_solution[0].departure.1
-                        >= chrono::Local.timestamp(when.timestamp(), 0)
-                        && filling_solution[&filling_solution.len() - 1].arrival.1
-                            <= chrono::Local
-                                .datetime_from_str(
-                                    soluzione.vehicles[0].orarioPartenza.as_str(),
-                                    "%FT%T",
-                                )
-                                .expect("Data non valida")
-                    {
-                        for filling_train in filling_solution {
-                            train_trips.push(filling_train.clone());
-                        }
-                        break;
-                    }
-                }
-            }
-            let mut old_to: Option<&str> = None;
-            let mut old_to_stn = to.clone();
-            let mut old_ts = chrono::Local.timestamp(when.timestamp(), 0);
-            for train_trip in soluzione.vehicles.iter() {
-                let from = &self.stations[*self
-                    .fast_station_lookup
-                    .get(train_trip.origine.as_ref().unwrap_or(&String::from("")))
-                    .expect("Inconsistency in Trenitalia")];
-                let to = &self.stations[*self
-                    .fast_station_lookup
-                    .get(
-                        train_trip
-                            .destinazione
-                            .as_ref()
-                            .unwrap_or(&String::from("")),
-                    )
-                    .expect("Inconsistency in Trenitalia")];
-                if old_to.is_some() && old_to != Some(&from.get_name()) {
-                    let filling_solutions = lefrecce::find_trips(&old_to_stn, from, &old_ts);
-                    for filling_solution in filling_solutions.iter() {
-                        if filling_solution[0].departure.1 >= old_ts
-                            && filling_solution[&filling_solution.len() - 1].arrival.1
-                                <= chrono::Local
-                                    .datetime_from_str(train_trip.orarioPartenza.as_str(), "%FT%T")
-                                    .expect("Data non valida")
-                        {
-                            for filling_train in filling_solution {
-                                train_trips.push(filling_train.clone());
-                            }
-                            break;
-                        }
-                    }
-                }
-                old_to = Some(&to.get_name());
-                old_to_stn = to.clone();
-                old_ts = chrono::Local
-                    .datetime_from_str(train_trip.orarioArrivo.as_str(), "%FT%T")
-                    .expect("Data non valida");
-                train_trips.push(TrainTrip {
-                    departure: (
-                        from.clone(),
-                        chrono::Local
-                            .datetime_from_str(train_trip.orarioPartenza.as_str(), "%FT%T")
-                            .expect("Data non valida"),
+            for part in viaggiatreno::plan_solution(self, soluzione, from, to, when)? {
+                match part {
+                    viaggiatreno::SolutionPart::Trip(trip) => train_trips.push(trip),
+                    viaggiatreno::SolutionPart::Gap(gap) => train_trips.extend(
+                        gap.fill(&lefrecce::find_trips(self, &gap.from, &gap.to, &gap.when)?),
                     ),
-                    arrival: (
-                        to.clone(),
-                        chrono::Local
-                            .datetime_from_str(train_trip.orarioArrivo.as_str(), "%FT%T")
-                            .expect("Data non valida"),
-                    ),
-                    train_number: utils::match_train_type(
-                        &train_trip.categoriaDescrizione,
-                        train_trip.numeroTreno.parse::<u32>().unwrap_or(
-                            train_trip
-                                .numeroTreno
-                                .chars()
-                                .into_iter()
-                                .map(|x| if x.is_digit(10) { x } else { '0' })
-                                .collect::<String>()
-                                .parse::<u32>()
-                                .unwrap(),
-                        ),
-                    ),
-                });
-            }
-            if cfg!(debug_assertions) {
-                println!(
-                    "expected: {}, found: {}, delta: {}",
-                    &to.get_name(),
-                    &soluzione.vehicles[&soluzione.vehicles.len() - 1]
-                        .destinazione
-                        .as_ref()
-                        .unwrap_or(&String::from("")),
-                    utils::match_strings(
-                        &soluzione.vehicles[&soluzione.vehicles.len() - 1]
-                            .destinazione
-                            .as_ref()
-                            .unwrap_or(&String::from("")),
-                        &to.get_name(),
-                    )
-                );
-            }
-            if utils::match_strings(
-                &soluzione.vehicles[&soluzione.vehicles.len() - 1]
-                    .destinazione
-                    .as_ref()
-                    .unwrap_or(&String::from("")),
-                &to.get_name(),
-            ) < WORDS_EQUALITY_THRESHOLD
-            {
-                let filling_from = &self.stations[*self
-                    .fast_station_lookup
-                    .get(
-                        soluzione.vehicles[&soluzione.vehicles.len() - 1]
-                            .destinazione
-                            .as_ref()
-                            .unwrap_or(&String::from("")),
-                    )
-                    .expect("Inconsistency in Trenitalia")];
-                if cfg!(debug_assertions) {
-                    println!("filling_from = {:?}", filling_from);
-                }
-                let filling_solutions = lefrecce::find_trips(filling_from, to, when);
-                for filling_solution in filling_solutions.iter() {
-                    if filling_solution[0].departure.1
-                        >= chrono::Local
-                            .datetime_from_str(
-                                soluzione.vehicles[&soluzione.vehicles.len() - 1]
-                                    .orarioArrivo
-                                    .as_str(),
-                                "%FT%T",
-                            )
-                            .expect("Data non valida")
-                    {
-                        for filling_train in filling_solution {
-                            train_trips.push(filling_train.clone());
-                        }
-                        break;
-                    }
                 }
             }
-            result.push(train_trips);
+            result.extend(Journey::new(train_trips, None));
         }
-        result
+        Ok(result)
     }
 
     /// Call to the ViaggiaTreno station lookup API
-    pub fn find_train_station_online(&self, name: &str) -> Option<&TrainStation> {
-        //return Some(&self.stations[0]);
-        let url = format!(
-            "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/autocompletaStazione/{}",
-            name
-        );
-        if cfg!(debug_assertions) {
-            println!("{}", url);
+    pub fn find_train_station_online(&self, name: &str) -> Result<Option<&TrainStation>, Error> {
+        let url = self.viaggiatreno_url(&format!("autocompletaStazione/{}", name));
+        let response = self.http_get(&url)?;
+        Ok(viaggiatreno::station_from_autocomplete(self, &response))
+    }
+
+    /// Get the trains leaving from a station, starting from a certain time
+    pub fn departures(
+        &self,
+        station: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<BoardEntry>, Error> {
+        self.board(viaggiatreno::Board::Departures, station, when)
+    }
+
+    /// Get the trains arriving at a station, starting from a certain time
+    pub fn arrivals(
+        &self,
+        station: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<BoardEntry>, Error> {
+        self.board(viaggiatreno::Board::Arrivals, station, when)
+    }
+
+    fn board(
+        &self,
+        board: viaggiatreno::Board,
+        station: &TrainStation,
+        when: &chrono::DateTime<chrono_tz::Tz>,
+    ) -> Result<Vec<BoardEntry>, Error> {
+        let url = viaggiatreno::board_url(self, board, station, when)?;
+        viaggiatreno::parse_board(self, board, &self.http_get(&url)?)
+    }
+
+    /// Return a station object reference that has the requested ID
+    pub fn get_train_station(&self, id: &str) -> Option<&TrainStation> {
+        self.stations.by_id(id)
+    }
+
+    /// Return the station that has the requested ViaggiaTreno ID (e.g. `S05043`)
+    pub fn get_train_station_by_vt_id(&self, vt_id: &str) -> Option<&TrainStation> {
+        self.stations.by_vt_id(vt_id)
+    }
+
+    /// Return the station that has the requested LeFrecce name, compared in its normal form
+    pub fn get_train_station_by_lefrecce_name(&self, name: &str) -> Option<&TrainStation> {
+        self.stations.by_lefrecce_name(name)
+    }
+
+    /// Return all the known stations
+    pub fn stations(&self) -> &[TrainStation] {
+        self.stations.all()
+    }
+
+    /// Return the stations in the region with the requested ViaggiaTreno region ID
+    pub fn stations_by_region_id(&self, region_id: u8) -> impl Iterator<Item = &TrainStation> {
+        self.stations
+            .all()
+            .iter()
+            .filter(move |station| station.region_id == region_id)
+    }
+
+    /// Return the stations in a region
+    pub fn stations_in_region(&self, region: Region) -> impl Iterator<Item = &TrainStation> {
+        self.stations_by_region_id(region.id())
+    }
+
+    /// Ask ViaggiaTreno the region of a station, which needs its ViaggiaTreno ID.
+    /// This fills in or checks the region of stations that are not in the station database.
+    pub fn station_region(&self, station: &TrainStation) -> Result<Region, Error> {
+        let url = viaggiatreno::region_url(self, station)?;
+        viaggiatreno::parse_region(&self.http_get(&url)?)
+    }
+
+    /// Download the ViaggiaTreno station lists of every region.
+    /// A station on a regional border may be listed more than once.
+    pub fn station_list(&self) -> Result<Vec<RemoteStation>, Error> {
+        let mut stations: Vec<RemoteStation> = Vec::new();
+        for region in Region::ALL.iter() {
+            let url = viaggiatreno::station_list_url(self, *region);
+            stations.extend(viaggiatreno::parse_station_list(&self.http_get(&url)?)?);
+        }
+        Ok(stations)
+    }
+
+    /// Compare the station database with the station lists downloaded by `station_list`.
+    /// The stations are matched by ViaggiaTreno ID and keep their code, while the new ones get
+    /// a code from `StationCodeGenerator`; a station is reported as moved when its position
+    /// changed by more than `moved_threshold_km`. Changes of name and region are reported too.
+    pub fn diff_stations(
+        &self,
+        remote: &[RemoteStation],
+        moved_threshold_km: f64,
+    ) -> Result<StationDiff, Error> {
+        refresh::diff(self.stations(), remote, moved_threshold_km)
+    }
+
+    /// Look for a train station
+    pub fn find_train_station(&self, name: &str) -> Option<&TrainStation> {
+        if let Some(station) = self.stations.by_name(name) {
+            return Some(station);
         }
-        let response = ureq::get(&url)
-            .call()
-            .expect("Failed API call")
-            .into_string()
-            .unwrap();
-        if response.len() == 0 {
+        let name = normalize(name);
+        if name.is_empty() {
             return None;
         }
-        let body: Vec<Vec<&str>> = response
-            .trim_end_matches('\n')
-            .split("\n")
-            .collect::<Vec<&str>>()
+        let mut min_diff = 0.0;
+        let mut found_station = None;
+        for (station, names) in self
+            .stations
+            .all()
             .iter()
-            .map(|&x| x.split("|").collect::<Vec<&str>>())
-            .collect();
-        if body.len() == 0 {
-            None
-        } else {
-            for station in &self.stations {
-                let vt_id = match &station.vt_id {
-                    Some(x) => Some(String::from(x)),
-                    None => None,
-                };
-
-                if station.vt_id.is_none() {
-                    continue;
-                } else if vt_id.unwrap() == body[0][1] {
+            .zip(self.stations.normalized_names())
+        {
+            for alias in &names.aliases {
+                let diff = utils::match_normalized(alias, &name);
+                if diff == 1.0 {
                     return Some(station);
                 }
+                if diff > min_diff {
+                    min_diff = diff;
+                    found_station = Some(station);
+                }
             }
-            None
         }
+        found_station.filter(|_| min_diff >= WORDS_EQUALITY_THRESHOLD)
     }
 
-    /// Return a station object reference that has the requested ID
-    pub fn get_train_station(&self, id: &str) -> Option<&TrainStation> {
-        if cfg!(debug_assertions) {
-            println!("{:?}", id);
+    /// Look for the stations that best match a name, sorted by decreasing similarity.
+    /// Each station is returned once, with its most similar alias; exact matches come first.
+    pub fn search_stations(&self, query: &str, limit: usize) -> Vec<StationMatch<'_>> {
+        let exact = self.stations.by_name(query);
+        let query = normalize(query);
+        if query.is_empty() {
+            return vec![];
         }
-        for station in &self.stations {
-            if &station.id == id {
-                return Some(station);
-            }
-        }
-        None
+        let mut matches: Vec<(bool, StationMatch)> = self
+            .stations
+            .all()
+            .iter()
+            .zip(self.stations.normalized_names())
+            .map(|(station, names)| {
+                let (alias, score) = station
+                    .aliases
+                    .iter()
+                    .zip(&names.aliases)
+                    .chain(station.lefrecce_name.iter().zip(&names.lefrecce_name))
+                    .map(|(alias, normalized)| {
+                        (alias.as_str(), utils::match_normalized(normalized, &query))
+                    })
+                    .fold(("", -1.0), |best, candidate| {
+                        if candidate.1 > best.1 {
+                            candidate
+                        } else {
+                            best
+                        }
+                    });
+                (
+                    exact.is_some_and(|exact| std::ptr::eq(exact, station)),
+                    StationMatch {
+                        station,
+                        alias,
+                        score,
+                    },
+                )
+            })
+            .collect();
+        matches.sort_by(|(a_exact, a), (b_exact, b)| {
+            b_exact
+                .cmp(a_exact)
+                .then(b.score.total_cmp(&a.score))
+                .then_with(|| a.station.get_name().cmp(b.station.get_name()))
+        });
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, station_match)| station_match)
+            .collect()
     }
 
-    /// Look for a train station
-    pub fn find_train_station(&self, name: &str) -> Option<&TrainStation> {
-        let mut min_diff = 0.0;
-        let mut found_station = &self.stations[0];
-        match self.fast_station_lookup.get(&name.to_uppercase()) {
-            Some(x) => return Some(&self.stations[*x]),
-            None => {
-                for station in &self.stations {
-                    for alias in &station.aliases {
-                        let diff = utils::match_strings(alias, &name);
-                        if cfg!(debug_assertions) {
-                            //println!("Difference between {} and {} = {}", &station.name, &name, diff);
-                        }
-                        if diff == 1.0 {
-                            return Some(station);
-                        }
-                        if diff > min_diff {
-                            min_diff = diff;
-                            found_station = station;
-                        }
-                    }
-                }
-                return if min_diff >= WORDS_EQUALITY_THRESHOLD {
-                    Some(found_station)
-                } else {
-                    None
-                };
-            }
-        };
+    /// Complete a partially typed station name, without accessing the network.
+    /// Every typed word has to start a word of the station name, e.g. "bo centr" finds
+    /// "Bologna Centrale".
+    pub fn autocomplete_stations(&self, prefix: &str, limit: usize) -> Vec<StationMatch<'_>> {
+        self.stations
+            .prefix_index()
+            .search(self.stations.all(), prefix, limit)
     }
 
     /// Get train details from ViaggiaTreno
-    fn train_info_raw(&self, number: u32, from: &str) -> TrainInfo {
-        let url =
-            format!(
-            "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/tratteCanvas/{}/{}/{}",
-            from, number, current_timestamp_ms!()
-        );
-        let response: Vec<mapping::VTDetailedTrainTripLeg> = serde_json::from_value(
-            ureq::get(&url)
-                .call()
-                .expect("Failed API call")
-                .into_json()
-                .unwrap(),
-        )
-        .unwrap();
+    fn train_info_raw(
+        &self,
+        number: u32,
+        candidate: &viaggiatreno::TrainCandidate,
+    ) -> Result<TrainInfo, Error> {
+        let url = viaggiatreno::train_info_url(self, number, candidate)?;
+        let response: mapping::VTTrainStatus = serde_json::from_str(&self.http_get(&url)?)?;
         TrainInfo::from(&response, self)
     }
 
+    /// Get the trains with a certain number
+    fn train_candidates(&self, number: u32) -> Result<Vec<viaggiatreno::TrainCandidate>, Error> {
+        viaggiatreno::parse_train_candidates(
+            &self.http_get(&viaggiatreno::train_search_url(self, number))?,
+        )
+    }
+
     /// Get train details, provided that you know the originating station
-    pub fn train_info(&self, number: u32, from: String) -> Result<TrainInfo, &str> {
-        let url = format!("http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/cercaNumeroTrenoTrenoAutocomplete/{}", number);
-        let response = ureq::get(&url)
-            .call()
-            .expect("Failed API call")
-            .into_string()
-            .unwrap();
-        let body: Vec<Vec<&str>> = response
-            .trim_end_matches('\n')
-            .split("\n")
-            .collect::<Vec<&str>>()
-            .iter()
-            .map(|&x| x.split("|").collect::<Vec<&str>>())
-            .collect();
-        let train_station_of_origination: &str = match body.len() {
-            1 => body[0][1].split('-').collect::<Vec<&str>>()[1],
-            0 => {
-                return Err("No train found");
-            }
-            _ => {
-                let mut station_code = "";
-                let mut min_diff = 0.0;
-                for option in body {
-                    let diff = utils::match_strings(
-                        &option[0].split('-').collect::<Vec<&str>>()[1]
-                            .trim_start()
-                            .to_lowercase(),
-                        &from.to_lowercase(),
-                    );
-                    if diff < min_diff {
-                        min_diff = diff;
-                        station_code = option[1].split('-').collect::<Vec<&str>>()[1];
-                    }
-                    if diff == 1.0 {
-                        break;
-                    }
-                }
-                if min_diff == 0.0 {
-                    return Err("Train not found");
-                } else {
-                    station_code
-                }
-            }
-        };
-        Ok(self.train_info_raw(number, train_station_of_origination))
+    pub fn train_info(&self, number: u32, from: String) -> Result<TrainInfo, Error> {
+        let candidates = self.train_candidates(number)?;
+        let train_of_origination = viaggiatreno::select_train_origin(&candidates, number, &from)?;
+        self.train_info_raw(number, train_of_origination)
     }
 
     /// Get train details, knowing that it calls at a certain station
@@ -490,49 +499,54 @@ impl Trenitalia {
         &self,
         number: u32,
         calling_at: &TrainStation,
-    ) -> Result<TrainInfo, &str> {
-        let url = format!("http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/cercaNumeroTrenoTrenoAutocomplete/{}", number);
-        let response = ureq::get(&url)
-            .call()
-            .expect("Failed API call")
-            .into_string()
-            .unwrap();
-        let body: Vec<Vec<&str>> = response
-            .trim_end_matches('\n')
-            .split("\n")
-            .collect::<Vec<&str>>()
-            .iter()
-            .map(|&x| x.split("|").collect::<Vec<&str>>())
-            .collect();
-        match body.len() {
-            1 => Ok(self.train_info_raw(number, body[0][1].split('-').collect::<Vec<&str>>()[1])),
-            0 => Err("Train not found"),
+    ) -> Result<TrainInfo, Error> {
+        let candidates = self.train_candidates(number)?;
+        match candidates.len() {
+            1 => self.train_info_raw(number, &candidates[0]),
+            0 => Err(Error::TrainNotFound(number)),
             _ => {
-                for option in body {
-                    let train_info =
-                        self.train_info_raw(number, option[1].split('-').collect::<Vec<&str>>()[1]);
-                    for stop in &train_info.stops {
-                        if stop.station.id == calling_at.id {
-                            return Ok(train_info);
-                        }
+                for candidate in &candidates {
+                    let train_info = self.train_info_raw(number, candidate)?;
+                    if train_info
+                        .stops
+                        .iter()
+                        .any(|stop| stop.station.id == calling_at.id)
+                    {
+                        return Ok(train_info);
                     }
                 }
-                return Err("Train not found");
+                Err(Error::AmbiguousTrain(number))
             }
         }
     }
     /// Finds the nearest station from a point
     pub fn nearest_station(&self, point: (f64, f64)) -> &TrainStation {
-        let mut min_dist = std::f64::MAX;
-        let mut sta = &self.stations[0];
-        for station in &self.stations {
-            let dist_sq =
-                (station.position.0 - point.0).powf(2.0) + (station.position.1 - point.1).powf(2.0);
-            if dist_sq < min_dist {
-                sta = station;
-                min_dist = dist_sq;
-            }
-        }
-        sta
+        self.nearest_stations(point, 1)[0].0
+    }
+
+    /// Finds the `k` stations nearest to a (latitude, longitude) point, closest first,
+    /// with their distance in kilometres
+    pub fn nearest_stations(&self, point: (f64, f64), k: usize) -> Vec<(&TrainStation, f64)> {
+        self.stations.grid().nearest(self.stations.all(), point, k)
+    }
+
+    /// Finds the stations within `radius_km` kilometres from a point, closest first,
+    /// with their distance in kilometres
+    pub fn stations_within(&self, point: (f64, f64), radius_km: f64) -> Vec<(&TrainStation, f64)> {
+        self.stations
+            .grid()
+            .within(self.stations.all(), point, radius_km)
+    }
+
+    /// Finds the stations in the box between the south-west and north-east corners, with their
+    /// distance in kilometres from the centre of the box, closest first
+    pub fn stations_in_box(
+        &self,
+        south_west: (f64, f64),
+        north_east: (f64, f64),
+    ) -> Vec<(&TrainStation, f64)> {
+        self.stations
+            .grid()
+            .in_box(self.stations.all(), south_west, north_east)
     }
 }
@@ -73,18 +73,60 @@ pub struct VTDetailedTrainTripStop {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct VTDetailedTrainTripLeg {
-    pub last: bool,
-    pub stazioneCorrente: bool,
-    pub id: String,
-    pub stazione: String,
-    pub fermata: VTDetailedTrainTripStop,
-    pub partenzaReale: bool,
-    pub arrivoReale: bool,
-    pub first: bool,
-    pub orientamento: Vec<String>,
-    pub nextTrattaType: Option<i8>,
-    pub actualFermataType: Option<i8>,
-    pub previousTrattaType: Option<i8>,
-    pub trattaType: i8,
+pub struct VTTrainStatus {
+    pub numeroTreno: u32,
+    pub categoria: Option<String>,
+    pub categoriaDescrizione: Option<String>,
+    pub compNumeroTreno: Option<String>,
+    pub origine: String,
+    pub idOrigine: Option<String>,
+    pub destinazione: String,
+    pub idDestinazione: Option<String>,
+    pub orarioPartenza: Option<u64>,
+    pub orarioArrivo: Option<u64>,
+    pub dataPartenzaTreno: Option<u64>,
+    #[serde(default)]
+    pub ritardo: i16,
+    pub stazioneUltimoRilevamento: Option<String>,
+    pub oraUltimoRilevamento: Option<u64>,
+    #[serde(default)]
+    pub provvedimento: i8,
+    pub riprogrammazione: Option<String>,
+    #[serde(default)]
+    pub haCambiNumero: bool,
+    pub subTitle: Option<String>,
+    pub fermate: Vec<VTDetailedTrainTripStop>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VTBoardEntry {
+    pub numeroTreno: u32,
+    pub categoria: Option<String>,
+    pub categoriaDescrizione: Option<String>,
+    pub origine: Option<String>,
+    pub destinazione: Option<String>,
+    pub codOrigine: Option<String>,
+    pub orarioPartenza: Option<i64>,
+    pub orarioArrivo: Option<i64>,
+    #[serde(default)]
+    pub ritardo: i16,
+    pub binarioProgrammatoPartenzaDescrizione: Option<String>,
+    pub binarioEffettivoPartenzaDescrizione: Option<String>,
+    pub binarioProgrammatoArrivoDescrizione: Option<String>,
+    pub binarioEffettivoArrivoDescrizione: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VTStationListEntry {
+    pub codiceStazione: String,
+    pub codReg: u8,
+    pub lat: f64,
+    pub lon: f64,
+    pub localita: VTLocality,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VTLocality {
+    pub nomeLungo: String,
+    pub nomeBreve: Option<String>,
 }
@@ -0,0 +1,69 @@
+//! Normalisation of station names, used both for the station database and for the queries
+//!
+//! This module only depends on the standard library, so that the build script can use it too.
+
+/// Abbreviations that are recognised as a whole, dots included
+pub(crate) const DOTTED_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("c.le", "centrale"),
+    ("p.ta", "porta"),
+    ("p.zza", "piazza"),
+    ("staz.", "stazione"),
+];
+
+/// Words that are written in more than one way
+const WORD_VARIANTS: &[(&str, &str)] = &[
+    ("sant", "san"),
+    ("santa", "san"),
+    ("santo", "san"),
+    ("staz", "stazione"),
+];
+
+/// Replaces accented letters with the plain ones
+pub(crate) fn fold_diacritic(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ä' | 'ã' => 'a',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ò' | 'ó' | 'ô' | 'ö' | 'õ' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ç' => 'c',
+        'ñ' => 'n',
+        _ => c,
+    }
+}
+
+fn push_word(words: &mut Vec<String>, word: &str) {
+    let word = WORD_VARIANTS
+        .iter()
+        .find(|(variant, _)| *variant == word)
+        .map_or(word, |(_, canonical)| canonical);
+    words.push(word.to_string());
+}
+
+/// Normalises a station name: the result is lowercase, without diacritics, apostrophes
+/// (the TSV files write "Forlì" as "Forli`") and punctuation, with single spaces between
+/// words and with the common abbreviations expanded, so "Bologna C.le" and "bologna centrale"
+/// or "S. Giovanni" and "San Giovanni" have the same normal form.
+pub fn normalize(name: &str) -> String {
+    let folded: String = name.to_lowercase().chars().map(fold_diacritic).collect();
+    let mut words: Vec<String> = Vec::new();
+    for token in folded
+        .split(|c: char| !c.is_alphanumeric() && c != '.')
+        .filter(|token| !token.is_empty())
+    {
+        if !token.contains('.') {
+            push_word(&mut words, token);
+        } else if let Some((_, expansion)) = DOTTED_ABBREVIATIONS
+            .iter()
+            .find(|(abbreviation, _)| *abbreviation == token)
+        {
+            words.push(expansion.to_string());
+        } else {
+            // "S.", "S.Giorgio" or "S.G.Cannitello": a lone "s" followed by a dot is "san"
+            for piece in token.split('.').filter(|piece| !piece.is_empty()) {
+                push_word(&mut words, if piece == "s" { "san" } else { piece });
+            }
+        }
+    }
+    words.join(" ")
+}
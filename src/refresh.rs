@@ -0,0 +1,261 @@
+//! Comparison of the station database with the station lists published by ViaggiaTreno
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use crate::normalize::{normalize, DOTTED_ABBREVIATIONS};
+use crate::{haversine_km, Error, StationCodeGenerator, TrainStation};
+
+/// A station from the ViaggiaTreno station list of a region
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoteStation {
+    /// ViaggiaTreno ID, e.g. `S05043`
+    pub vt_id: String,
+    /// Name, in uppercase
+    pub name: String,
+    pub region_id: u8,
+    /// Tuple that contains latitude and longitude
+    pub position: (f64, f64),
+}
+
+/// A difference between the station database and the ViaggiaTreno station lists
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StationChange {
+    /// A station that is not in the database, with its new code
+    Added(TrainStation),
+    /// A station that ViaggiaTreno doesn't list anymore
+    Removed(TrainStation),
+    /// A station with a new name; the old name is kept as an alias
+    Renamed { station: TrainStation, from: String },
+    /// A station that ViaggiaTreno lists in another region
+    RegionChanged { station: TrainStation, from: u8 },
+    /// A station whose position is farther than the threshold from the one in the database
+    Moved {
+        station: TrainStation,
+        from: (f64, f64),
+        distance_km: f64,
+    },
+}
+
+/// The result of `Trenitalia::diff_stations`
+#[derive(Debug, Clone)]
+pub struct StationDiff {
+    pub changes: Vec<StationChange>,
+    /// The updated station list, with the codes of the existing stations unchanged
+    pub stations: Vec<TrainStation>,
+}
+
+/// Capitalises the first letter of every word of a ViaggiaTreno name, like the names in
+/// `stations.tsv` ("CASTELFRANCO D'EMILIA" becomes "Castelfranco D'Emilia" and "S.GIORGIO"
+/// becomes "S.Giorgio"), except after the dot of the abbreviations ("C.LE" becomes "C.le")
+fn title_case(name: &str) -> String {
+    name.split_inclusive(|c: char| !c.is_alphanumeric() && c != '.')
+        .flat_map(|piece| {
+            let word = piece.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '.');
+            let abbreviation = DOTTED_ABBREVIATIONS
+                .iter()
+                .any(|(abbreviation, _)| *abbreviation == word.to_lowercase());
+            let mut capitalise = true;
+            piece.chars().flat_map(move |c| {
+                let converted: Vec<char> = if capitalise {
+                    c.to_uppercase().collect()
+                } else {
+                    c.to_lowercase().collect()
+                };
+                capitalise = if c == '.' {
+                    !abbreviation
+                } else {
+                    !c.is_alphabetic()
+                };
+                converted
+            })
+        })
+        .collect()
+}
+
+/// Compares a station list with the ViaggiaTreno one, see `Trenitalia::diff_stations`
+pub(crate) fn diff(
+    stations: &[TrainStation],
+    remote: &[RemoteStation],
+    moved_threshold_km: f64,
+) -> Result<StationDiff, Error> {
+    // A station on a regional border is listed by both regions
+    let mut remote_by_vt_id: HashMap<&str, &RemoteStation> = HashMap::new();
+    for station in remote {
+        remote_by_vt_id
+            .entry(station.vt_id.as_str())
+            .or_insert(station);
+    }
+    let mut generator =
+        StationCodeGenerator::with_used(stations.iter().filter_map(|s| s.code().ok()));
+    let mut changes: Vec<StationChange> = Vec::new();
+    let mut updated: Vec<TrainStation> = Vec::new();
+    let mut known: HashSet<&str> = HashSet::new();
+    for station in stations {
+        let vt_id = match &station.vt_id {
+            Some(vt_id) => vt_id,
+            // It can't be compared
+            None => {
+                updated.push(station.clone());
+                continue;
+            }
+        };
+        known.insert(vt_id);
+        let remote = match remote_by_vt_id.get(vt_id.as_str()) {
+            Some(remote) => remote,
+            None => {
+                changes.push(StationChange::Removed(station.clone()));
+                continue;
+            }
+        };
+        let mut station = station.clone();
+        if station.region_id != remote.region_id {
+            let from = std::mem::replace(&mut station.region_id, remote.region_id);
+            changes.push(StationChange::RegionChanged {
+                station: station.clone(),
+                from,
+            });
+        }
+        let distance_km = haversine_km(station.position, remote.position);
+        if distance_km > moved_threshold_km {
+            let from = station.position;
+            station.position = remote.position;
+            changes.push(StationChange::Moved {
+                station: station.clone(),
+                from,
+                distance_km,
+            });
+        }
+        if normalize(station.get_name()) != normalize(&remote.name) {
+            let from = std::mem::replace(&mut station.aliases[0], title_case(&remote.name));
+            if !station.aliases[1..]
+                .iter()
+                .any(|alias| normalize(alias) == normalize(&from))
+            {
+                station.aliases.insert(1, from.clone());
+            }
+            changes.push(StationChange::Renamed {
+                station: station.clone(),
+                from,
+            });
+        }
+        updated.push(station);
+    }
+    let mut added: Vec<&RemoteStation> = remote
+        .iter()
+        .filter(|station| known.insert(&station.vt_id))
+        .collect();
+    added.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.vt_id.cmp(&b.vt_id)));
+    for remote in added {
+        let station = TrainStation {
+            id: generator.generate(&remote.name)?.to_string(),
+            region_id: remote.region_id,
+            position: remote.position,
+            aliases: vec![title_case(&remote.name)],
+            vt_id: Some(remote.vt_id.clone()),
+            lefrecce_name: None,
+        };
+        changes.push(StationChange::Added(station.clone()));
+        updated.push(station);
+    }
+    Ok(StationDiff {
+        changes,
+        stations: updated,
+    })
+}
+
+fn write_lines(file: &str, mut writer: impl Write, lines: Vec<String>) -> Result<(), Error> {
+    writer
+        .write_all(lines.join("\n").as_bytes())
+        .map_err(|error| Error::StationData {
+            file: file.to_string(),
+            line: None,
+            message: error.to_string(),
+        })
+}
+
+fn create(path: &Path) -> Result<std::fs::File, Error> {
+    std::fs::File::create(path).map_err(|error| Error::StationData {
+        file: path.display().to_string(),
+        line: None,
+        message: error.to_string(),
+    })
+}
+
+impl StationDiff {
+    /// Writes the updated station list in the format read by `Trenitalia::from_readers`
+    pub fn write(
+        &self,
+        stations: impl Write,
+        aliases: impl Write,
+        lefrecce_names: impl Write,
+        vt_ids: impl Write,
+    ) -> Result<(), Error> {
+        let position = |value: f64| format!("{}", value);
+        write_lines(
+            "stations.tsv",
+            stations,
+            self.stations
+                .iter()
+                .map(|station| {
+                    [
+                        station.get_name(),
+                        &station.id,
+                        &station.region_id.to_string(),
+                        &position(station.position.0),
+                        &position(station.position.1),
+                    ]
+                    .join("\t")
+                })
+                .collect(),
+        )?;
+        write_lines(
+            "aliases.tsv",
+            aliases,
+            self.stations
+                .iter()
+                .flat_map(|station| {
+                    station.aliases[1..]
+                        .iter()
+                        .map(move |alias| format!("{}\t{}", alias, station.id))
+                })
+                .collect(),
+        )?;
+        write_lines(
+            "id_lf_map.tsv",
+            lefrecce_names,
+            self.stations
+                .iter()
+                .filter_map(|station| {
+                    let name = station.lefrecce_name.as_ref()?;
+                    Some(format!("{}\t{}", station.id, name))
+                })
+                .collect(),
+        )?;
+        write_lines(
+            "id_vt.tsv",
+            vt_ids,
+            self.stations
+                .iter()
+                .filter_map(|station| {
+                    let vt_id = station.vt_id.as_ref()?;
+                    Some(format!("{}\t{}", station.id, vt_id))
+                })
+                .collect(),
+        )
+    }
+
+    /// Writes `stations.tsv`, `aliases.tsv`, `id_lf_map.tsv` and `id_vt.tsv` in a directory
+    pub fn write_files(&self, directory: impl AsRef<Path>) -> Result<(), Error> {
+        let directory = directory.as_ref();
+        self.write(
+            create(&directory.join("stations.tsv"))?,
+            create(&directory.join("aliases.tsv"))?,
+            create(&directory.join("id_lf_map.tsv"))?,
+            create(&directory.join("id_vt.tsv"))?,
+        )
+    }
+}
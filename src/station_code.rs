@@ -0,0 +1,220 @@
+//! Three-letter station codes, their integer encoding and the generator of new codes,
+//! ported from `id_gen.py`
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use crate::normalize::fold_diacritic;
+use crate::Error;
+
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Three-letter station code, such as `BAC` for Bologna Centrale
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct StationCode([u8; 3]);
+
+impl StationCode {
+    /// Parses a code made of three uppercase letters from A to Z
+    pub fn parse(code: &str) -> Result<Self, Error> {
+        match code.as_bytes() {
+            &[a, b, c] if [a, b, c].iter().all(u8::is_ascii_uppercase) => {
+                Ok(StationCode([a, b, c]))
+            }
+            _ => Err(Error::InvalidStationCode(code.to_string())),
+        }
+    }
+
+    /// Packs the code in an integer, with 5 bits per letter and the first letter in the
+    /// lowest bits (`A` is 0)
+    pub fn encode(self) -> u16 {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, letter)| u16::from(letter - b'A') << (i * 5))
+            .sum()
+    }
+
+    /// Unpacks a code packed by `encode`
+    pub fn decode(value: u16) -> Result<Self, Error> {
+        let mut code = [0; 3];
+        for (i, letter) in code.iter_mut().enumerate() {
+            let offset = (value >> (i * 5)) & 0x1f;
+            *letter = *ALPHABET
+                .get(usize::from(offset))
+                .ok_or_else(|| Error::InvalidStationCode(value.to_string()))?;
+        }
+        if value >> 15 != 0 {
+            return Err(Error::InvalidStationCode(value.to_string()));
+        }
+        Ok(StationCode(code))
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("station codes are ASCII")
+    }
+}
+
+impl std::str::FromStr for StationCode {
+    type Err = Error;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        Self::parse(code)
+    }
+}
+
+impl TryFrom<String> for StationCode {
+    type Error = Error;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        Self::parse(&code)
+    }
+}
+
+impl From<StationCode> for String {
+    fn from(code: StationCode) -> Self {
+        code.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for StationCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Splits a station name in uppercase words.
+///
+/// Apostrophes and backticks are dropped and every other character that is not a letter
+/// separates two words. This differs from `id_gen.py`, which only splits on spaces, dashes,
+/// dots and slashes and keeps the other characters: its code for "Costigliole (Motta Di)" is
+/// `C(D`, which is not a valid `StationCode`, while here it's `CMD`.
+fn words(name: &str) -> Vec<Vec<u8>> {
+    let mut words: Vec<Vec<u8>> = vec![vec![]];
+    for c in name.to_lowercase().chars().map(fold_diacritic) {
+        match c {
+            '`' | '\'' => {}
+            'a'..='z' => words.last_mut().unwrap().push(c.to_ascii_uppercase() as u8),
+            _ => words.push(vec![]),
+        }
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// Generates the codes of new stations from their names, avoiding the codes already in use.
+///
+/// The codes depend on the order in which the stations are added. They follow the rules of
+/// `id_gen.py`, except that names are split in words of letters only (see `words`), so the
+/// codes are always valid `StationCode`s.
+#[derive(Debug, Clone, Default)]
+pub struct StationCodeGenerator {
+    used: HashSet<StationCode>,
+}
+
+impl StationCodeGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a generator that won't return any of `codes`
+    pub fn with_used(codes: impl IntoIterator<Item = StationCode>) -> Self {
+        StationCodeGenerator {
+            used: codes.into_iter().collect(),
+        }
+    }
+
+    /// Marks a code as used, returning whether it was free
+    pub fn reserve(&mut self, code: StationCode) -> bool {
+        self.used.insert(code)
+    }
+
+    pub fn is_used(&self, code: StationCode) -> bool {
+        self.used.contains(&code)
+    }
+
+    fn is_free(&self, code: &[u8]) -> bool {
+        !self
+            .used
+            .contains(&StationCode([code[0], code[1], code[2]]))
+    }
+
+    /// Generates and reserves a code for a station name
+    pub fn generate(&mut self, name: &str) -> Result<StationCode, Error> {
+        let words = words(name);
+        let first = words
+            .first()
+            .ok_or_else(|| Error::InvalidStationCode(name.to_string()))?;
+        // The first letter and the last letters of the first word, as many as needed to
+        // reach three letters with the initials of the other words
+        let first_name_len = 4usize.saturating_sub(words.len()).max(1);
+        let mut code = vec![first[0]];
+        code.extend_from_slice(&first[first.len().saturating_sub(first_name_len - 1)..]);
+        code.extend(words[1..].iter().map(|word| word[0]));
+        // Filled with the longest word, reversed
+        let longest = words.iter().fold(first, |longest, word| {
+            if word.len() > longest.len() {
+                word
+            } else {
+                longest
+            }
+        });
+        let from_end = code.len().min(4);
+        code.truncate(3);
+        if from_end <= longest.len() {
+            code.extend(longest[..=longest.len() - from_end].iter().rev());
+        }
+        code.truncate(3);
+        // Names with a single word of one letter
+        while code.len() < 3 {
+            code.push(*code.last().unwrap());
+        }
+
+        let last = words.last().unwrap();
+        let mut iteration = 1;
+        while !self.is_free(&code) && iteration < last.len() {
+            code[2] = last[last.len() - iteration];
+            iteration += 1;
+        }
+        if !self.is_free(&code) {
+            let (a, b, c) = (code[0], code[1], code[2]);
+            if let Some(permutation) = [[a, c, b], [b, a, c], [b, c, a], [c, a, b], [c, b, a]]
+                .iter()
+                .find(|permutation| self.is_free(&permutation[..]))
+            {
+                code = permutation.to_vec();
+            }
+        }
+        if !self.is_free(&code) && words.len() > 3 {
+            if let Some(word) = words[3..]
+                .iter()
+                .find(|word| self.is_free(&[code[0], code[1], word[0]]))
+            {
+                code[2] = word[0];
+            }
+        }
+        while !self.is_free(&code) {
+            // Like the script, every free replacement overwrites the previous one
+            for &letter in ALPHABET {
+                let replacements = [
+                    [code[0], code[1], letter],
+                    [code[0], letter, code[2]],
+                    [code[0], letter, letter],
+                    [letter, code[1], code[2]],
+                ];
+                for replacement in replacements.iter() {
+                    if self.is_free(replacement) {
+                        code = replacement.to_vec();
+                    }
+                }
+            }
+        }
+        let code = StationCode([code[0], code[1], code[2]]);
+        self.used.insert(code);
+        Ok(code)
+    }
+}
@@ -0,0 +1,207 @@
+//! Parsing of the TSV files of the station database and the lookup tables built on them
+//!
+//! This module only depends on the standard library (and on `normalize`), so that the build
+//! script can use it too.
+
+use std::collections::HashMap;
+
+use crate::normalize::normalize;
+
+pub(crate) const STATIONS_FILE: &str = "stations.tsv";
+pub(crate) const ALIASES_FILE: &str = "aliases.tsv";
+pub(crate) const LEFRECCE_NAMES_FILE: &str = "id_lf_map.tsv";
+pub(crate) const VT_IDS_FILE: &str = "id_vt.tsv";
+
+/// Contents of the TSV files that make up the station database
+pub(crate) struct StationFiles<'a> {
+    /// Name, ID, region ID, latitude and longitude of every station
+    pub(crate) stations: &'a str,
+    /// Alias and ID of the station
+    pub(crate) aliases: &'a str,
+    /// ID of the station and LeFrecce name
+    pub(crate) lefrecce_names: &'a str,
+    /// ID of the station and ViaggiaTreno ID
+    pub(crate) vt_ids: &'a str,
+}
+
+/// A station as described by the TSV files
+pub(crate) struct StationRow {
+    pub(crate) id: String,
+    /// The name of the station, followed by its aliases
+    pub(crate) aliases: Vec<String>,
+    pub(crate) region_id: u8,
+    pub(crate) position: (f64, f64),
+    pub(crate) vt_id: Option<String>,
+    pub(crate) lefrecce_name: Option<String>,
+}
+
+/// An invalid line of a TSV file, or an invalid file when `line` is `None`
+pub(crate) struct TableError {
+    pub(crate) file: &'static str,
+    pub(crate) line: Option<usize>,
+    pub(crate) message: String,
+}
+
+impl std::fmt::Display for TableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// Splits a TSV file in its non-empty lines, numbered from 1, and their columns
+fn rows(contents: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, row)| !row.trim().is_empty())
+        .map(|(i, row)| (i + 1, row.split('\t').collect()))
+}
+
+/// Parses a TSV file with two columns
+fn pairs<'a>(
+    file: &'static str,
+    contents: &'a str,
+) -> Result<Vec<(usize, &'a str, &'a str)>, TableError> {
+    rows(contents)
+        .map(|(line, columns)| match columns[..] {
+            [first, second] => Ok((line, first, second)),
+            _ => Err(TableError {
+                file,
+                line: Some(line),
+                message: format!("expected 2 columns, found {}", columns.len()),
+            }),
+        })
+        .collect()
+}
+
+fn station_index(
+    by_id: &HashMap<String, usize>,
+    file: &'static str,
+    line: usize,
+    id: &str,
+) -> Result<usize, TableError> {
+    by_id.get(id).copied().ok_or_else(|| TableError {
+        file,
+        line: Some(line),
+        message: format!("unknown station id: {}", id),
+    })
+}
+
+/// Parses the stations from the contents of the four TSV files
+pub(crate) fn parse(files: &StationFiles) -> Result<Vec<StationRow>, TableError> {
+    let mut stations: Vec<StationRow> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    for (line, columns) in rows(files.stations) {
+        let error = |message: String| TableError {
+            file: STATIONS_FILE,
+            line: Some(line),
+            message,
+        };
+        let (name, id, region_id, latitude, longitude) = match columns[..] {
+            [name, id, region_id, latitude, longitude] => {
+                (name, id, region_id, latitude, longitude)
+            }
+            _ => {
+                return Err(error(format!(
+                    "expected 5 columns, found {}",
+                    columns.len()
+                )))
+            }
+        };
+        if by_id.insert(id.to_string(), stations.len()).is_some() {
+            return Err(error(format!("duplicate station id: {}", id)));
+        }
+        stations.push(StationRow {
+            id: id.to_string(),
+            aliases: vec![name.to_string()],
+            position: (
+                latitude
+                    .parse::<f64>()
+                    .map_err(|_| error(format!("invalid latitude: {}", latitude)))?,
+                longitude
+                    .parse::<f64>()
+                    .map_err(|_| error(format!("invalid longitude: {}", longitude)))?,
+            ),
+            region_id: region_id
+                .parse::<u8>()
+                .map_err(|_| error(format!("invalid region id: {}", region_id)))?,
+            lefrecce_name: None,
+            vt_id: None,
+        });
+    }
+    // The station searches always have a station to fall back to
+    if stations.is_empty() {
+        return Err(TableError {
+            file: STATIONS_FILE,
+            line: None,
+            message: "no stations".to_string(),
+        });
+    }
+    for (line, alias, id) in pairs(ALIASES_FILE, files.aliases)? {
+        let i = station_index(&by_id, ALIASES_FILE, line, id)?;
+        stations[i].aliases.push(alias.to_string());
+    }
+    for (line, id, name) in pairs(LEFRECCE_NAMES_FILE, files.lefrecce_names)? {
+        let i = station_index(&by_id, LEFRECCE_NAMES_FILE, line, id)?;
+        // Some stations are listed without a LeFrecce name
+        if !name.is_empty() {
+            stations[i].lefrecce_name = Some(name.to_string());
+        }
+    }
+    for (line, id, vt_id) in pairs(VT_IDS_FILE, files.vt_ids)? {
+        let i = station_index(&by_id, VT_IDS_FILE, line, id)?;
+        stations[i].vt_id = Some(vt_id.to_string());
+    }
+    Ok(stations)
+}
+
+/// Tables that match every way a station can be referenced to its index in the station list
+pub(crate) struct Lookups {
+    /// Matches normalised aliases and LeFrecce names
+    pub(crate) by_name: HashMap<String, usize>,
+    pub(crate) by_id: HashMap<String, usize>,
+    pub(crate) by_vt_id: HashMap<String, usize>,
+    /// Matches normalised LeFrecce names
+    pub(crate) by_lefrecce_name: HashMap<String, usize>,
+}
+
+impl Lookups {
+    /// Indexes a list of stations
+    pub(crate) fn new(stations: &[StationRow]) -> Self {
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        let mut by_id: HashMap<String, usize> = HashMap::new();
+        let mut by_vt_id: HashMap<String, usize> = HashMap::new();
+        let mut by_lefrecce_name: HashMap<String, usize> = HashMap::new();
+        for (i, station) in stations.iter().enumerate() {
+            // A name made only of punctuation has no normal form and can't be looked up
+            for alias in station.aliases.iter().map(|alias| normalize(alias)) {
+                if !alias.is_empty() {
+                    by_name.insert(alias, i);
+                }
+            }
+            by_id.entry(station.id.clone()).or_insert(i);
+            if let Some(vt_id) = &station.vt_id {
+                by_vt_id.entry(vt_id.clone()).or_insert(i);
+            }
+            if let Some(lefrecce_name) = station.lefrecce_name.as_deref().map(normalize) {
+                // Some stations share the LeFrecce name (e.g. the high speed platforms):
+                // the first one is the main station
+                if !lefrecce_name.is_empty() {
+                    by_lefrecce_name.entry(lefrecce_name).or_insert(i);
+                }
+            }
+        }
+        for (name, &i) in &by_lefrecce_name {
+            by_name.entry(name.clone()).or_insert(i);
+        }
+        Lookups {
+            by_name,
+            by_id,
+            by_vt_id,
+            by_lefrecce_name,
+        }
+    }
+}
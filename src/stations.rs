@@ -0,0 +1,229 @@
+//! The station database, with the lookup tables and indexes built on it
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::normalize::normalize;
+use crate::station_tables::{
+    self, Lookups, StationFiles, StationRow, ALIASES_FILE, LEFRECCE_NAMES_FILE, STATIONS_FILE,
+    VT_IDS_FILE,
+};
+use crate::types::TrainStation;
+use crate::{autocomplete, geo, Error};
+
+fn read_to_string(file: &str, mut reader: impl Read) -> Result<String, Error> {
+    let mut contents = String::new();
+    reader
+        .read_to_string(&mut contents)
+        .map_err(|error| Error::StationData {
+            file: file.to_string(),
+            line: None,
+            message: error.to_string(),
+        })?;
+    Ok(contents)
+}
+
+fn open(path: &Path) -> Result<std::fs::File, Error> {
+    std::fs::File::open(path).map_err(|error| Error::StationData {
+        file: path.display().to_string(),
+        line: None,
+        message: error.to_string(),
+    })
+}
+
+/// A station of the embedded station database
+struct EmbeddedStation {
+    id: &'static str,
+    aliases: &'static [&'static str],
+    region_id: u8,
+    position: (f64, f64),
+    vt_id: Option<&'static str>,
+    lefrecce_name: Option<&'static str>,
+}
+
+// The embedded station database and its lookup tables, generated by the build script
+include!(concat!(env!("OUT_DIR"), "/stations.rs"));
+
+/// A table that matches a key to an index of the `stations` vector
+enum Lookup {
+    /// Perfect-hash map generated by the build script
+    Static(&'static phf::Map<&'static str, usize>),
+    Dynamic(HashMap<String, usize>),
+}
+
+impl Lookup {
+    fn get(&self, key: &str) -> Option<usize> {
+        match self {
+            Self::Static(map) => map.get(key).copied(),
+            Self::Dynamic(map) => map.get(key).copied(),
+        }
+    }
+}
+
+/// The list of stations, with a lookup table for every way a station can be referenced
+pub(crate) struct StationDatabase {
+    stations: Vec<TrainStation>,
+    /// Matches normalised aliases and LeFrecce names
+    by_name: Lookup,
+    by_id: Lookup,
+    by_vt_id: Lookup,
+    /// Matches normalised LeFrecce names
+    by_lefrecce_name: Lookup,
+    /// Index of the words in the station aliases, used for autocompletion and built when needed
+    prefix_index: OnceLock<autocomplete::PrefixIndex>,
+    /// Grid of the station positions, used for the spatial queries and built when needed
+    grid: OnceLock<geo::GridIndex>,
+    /// Normal form of the station names, used for the fuzzy searches and built when needed
+    normalized_names: OnceLock<Vec<NormalizedNames>>,
+}
+
+/// The aliases and the LeFrecce name of a station, in their normal form
+pub(crate) struct NormalizedNames {
+    pub(crate) aliases: Vec<String>,
+    pub(crate) lefrecce_name: Option<String>,
+}
+
+impl StationDatabase {
+    /// Indexes a list of stations
+    fn new(stations: Vec<StationRow>) -> Self {
+        let lookups = Lookups::new(&stations);
+        StationDatabase {
+            stations: stations
+                .into_iter()
+                .map(|station| TrainStation {
+                    id: station.id,
+                    region_id: station.region_id,
+                    position: station.position,
+                    aliases: station.aliases,
+                    vt_id: station.vt_id,
+                    lefrecce_name: station.lefrecce_name,
+                })
+                .collect(),
+            by_name: Lookup::Dynamic(lookups.by_name),
+            by_id: Lookup::Dynamic(lookups.by_id),
+            by_vt_id: Lookup::Dynamic(lookups.by_vt_id),
+            by_lefrecce_name: Lookup::Dynamic(lookups.by_lefrecce_name),
+            prefix_index: OnceLock::new(),
+            grid: OnceLock::new(),
+            normalized_names: OnceLock::new(),
+        }
+    }
+
+    /// Loads the station database embedded in the crate, whose lookup tables have been
+    /// compiled by the build script
+    pub(crate) fn embedded() -> Self {
+        StationDatabase {
+            stations: STATIONS
+                .iter()
+                .map(|station| TrainStation {
+                    id: station.id.to_string(),
+                    region_id: station.region_id,
+                    position: station.position,
+                    aliases: station.aliases.iter().map(|&alias| alias.into()).collect(),
+                    vt_id: station.vt_id.map(String::from),
+                    lefrecce_name: station.lefrecce_name.map(String::from),
+                })
+                .collect(),
+            by_name: Lookup::Static(&BY_NAME),
+            by_id: Lookup::Static(&BY_ID),
+            by_vt_id: Lookup::Static(&BY_VT_ID),
+            by_lefrecce_name: Lookup::Static(&BY_LEFRECCE_NAME),
+            prefix_index: OnceLock::new(),
+            grid: OnceLock::new(),
+            normalized_names: OnceLock::new(),
+        }
+    }
+
+    /// Parses the station list from the contents of the four TSV files
+    pub(crate) fn parse(files: &StationFiles) -> Result<Self, Error> {
+        Ok(Self::new(station_tables::parse(files)?))
+    }
+
+    /// Reads and parses the four TSV files
+    pub(crate) fn from_readers(
+        stations: impl Read,
+        aliases: impl Read,
+        lefrecce_names: impl Read,
+        vt_ids: impl Read,
+    ) -> Result<Self, Error> {
+        Self::parse(&StationFiles {
+            stations: &read_to_string(STATIONS_FILE, stations)?,
+            aliases: &read_to_string(ALIASES_FILE, aliases)?,
+            lefrecce_names: &read_to_string(LEFRECCE_NAMES_FILE, lefrecce_names)?,
+            vt_ids: &read_to_string(VT_IDS_FILE, vt_ids)?,
+        })
+    }
+
+    /// Opens, reads and parses the four TSV files
+    pub(crate) fn from_files(
+        stations: &Path,
+        aliases: &Path,
+        lefrecce_names: &Path,
+        vt_ids: &Path,
+    ) -> Result<Self, Error> {
+        Self::from_readers(
+            open(stations)?,
+            open(aliases)?,
+            open(lefrecce_names)?,
+            open(vt_ids)?,
+        )
+    }
+
+    pub(crate) fn all(&self) -> &[TrainStation] {
+        &self.stations
+    }
+
+    pub(crate) fn prefix_index(&self) -> &autocomplete::PrefixIndex {
+        self.prefix_index
+            .get_or_init(|| autocomplete::PrefixIndex::new(&self.stations))
+    }
+
+    pub(crate) fn grid(&self) -> &geo::GridIndex {
+        self.grid
+            .get_or_init(|| geo::GridIndex::new(&self.stations))
+    }
+
+    /// Returns the normalised names of every station, in the order of `all`
+    pub(crate) fn normalized_names(&self) -> &[NormalizedNames] {
+        self.normalized_names.get_or_init(|| {
+            self.stations
+                .iter()
+                .map(|station| NormalizedNames {
+                    aliases: station
+                        .aliases
+                        .iter()
+                        .map(|alias| normalize(alias))
+                        .collect(),
+                    lefrecce_name: station.lefrecce_name.as_deref().map(normalize),
+                })
+                .collect()
+        })
+    }
+
+    fn get(&self, lookup: &Lookup, key: &str) -> Option<&TrainStation> {
+        lookup.get(key).map(|i| &self.stations[i])
+    }
+
+    /// Returns the station with an alias or LeFrecce name, compared in their normal form.
+    /// A name without a normal form, e.g. made only of punctuation, matches no station.
+    pub(crate) fn by_name(&self, name: &str) -> Option<&TrainStation> {
+        match normalize(name) {
+            name if name.is_empty() => None,
+            name => self.get(&self.by_name, &name),
+        }
+    }
+
+    pub(crate) fn by_id(&self, id: &str) -> Option<&TrainStation> {
+        self.get(&self.by_id, id)
+    }
+
+    pub(crate) fn by_vt_id(&self, vt_id: &str) -> Option<&TrainStation> {
+        self.get(&self.by_vt_id, vt_id)
+    }
+
+    pub(crate) fn by_lefrecce_name(&self, name: &str) -> Option<&TrainStation> {
+        self.get(&self.by_lefrecce_name, &normalize(name))
+    }
+}
@@ -11,7 +11,13 @@ fn can_find_trips_between_two_stations_on_same_line() {
     let t = Trenitalia::new();
     let bologna = t.find_train_station("bologna centrale").unwrap();
     let cesena = t.nearest_station((44.133333, 12.233333));
-    let trips = t.find_trips(bologna, cesena, &chrono::Local::now());
+    let trips = t
+        .find_trips(
+            bologna,
+            cesena,
+            &chrono::Utc::now().with_timezone(&TIMEZONE),
+        )
+        .unwrap();
     assert!(!trips.is_empty());
 }
 
@@ -21,3 +27,1152 @@ fn can_obtain_exsisting_train_info() {
     let result = t.train_info(3905, "Piacenza".to_string());
     assert!(result.is_ok());
 }
+
+#[test]
+fn station_search_ranks_candidates() {
+    let t = Trenitalia::new();
+    let results = t.search_stations("San Giorgio", 3);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].station.id, "SNI");
+    assert_eq!(results[0].alias, "San Giorgio");
+    assert_eq!(results[0].score, 1.0);
+    assert!(results
+        .windows(2)
+        .all(|pair| pair[0].score >= pair[1].score));
+    // The LeFrecce name is an exact hit, ranked before the fuzzy ones
+    let results = t.search_stations("BOLOGNA CENTRALE", 2);
+    assert_eq!(results[0].station.id, "BAC");
+    assert_ne!(results[1].station.id, "BAC");
+    // Punctuation alone matches nothing
+    assert!(t.search_stations("!!!", 3).is_empty());
+    assert!(t.find_train_station("!!!").is_none());
+    assert!(t.find_train_station("-").is_none());
+}
+
+#[test]
+fn station_names_are_autocompleted() {
+    let t = Trenitalia::new();
+    let results = t.autocomplete_stations("bo centr", 5);
+    assert_eq!(results[0].station.id, "BAC");
+    assert!(t
+        .autocomplete_stations("centrale", 50)
+        .iter()
+        .any(|result| result.station.id == "BAC"));
+    let results = t.autocomplete_stations("Villa", 100);
+    assert!(results.len() > 10);
+    assert!(results
+        .iter()
+        .all(|result| result.alias.to_lowercase().contains("villa")));
+    assert!(t.autocomplete_stations("", 5).is_empty());
+    assert!(t.autocomplete_stations("xyzzy", 5).is_empty());
+}
+
+#[test]
+fn station_names_are_normalised() {
+    assert_eq!(normalize("Bologna C.le"), "bologna centrale");
+    assert_eq!(normalize("Forli`"), normalize("Forlì"));
+    assert_eq!(normalize("FORLI'"), "forli");
+    assert_eq!(normalize("S. Giovanni"), "san giovanni");
+    assert_eq!(normalize("Villa S.G.Cannitello"), "villa san g cannitello");
+    assert_eq!(normalize("Sant'Ilario d'Enza"), "san ilario d enza");
+    assert_eq!(normalize("Roma P.ta San Paolo"), "roma porta san paolo");
+    assert_eq!(normalize("Acquedolci-S.F."), "acquedolci san f");
+    let t = Trenitalia::new();
+    assert_eq!(t.find_train_station("Forlì").unwrap().id, "FLL");
+    assert_eq!(t.find_train_station("bologna c.le").unwrap().id, "BAC");
+    assert_eq!(
+        t.find_train_station("S. Giorgio").unwrap().id,
+        t.find_train_station("San Giorgio").unwrap().id
+    );
+    assert_eq!(t.autocomplete_stations("forlì", 1)[0].station.id, "FLL");
+}
+
+#[test]
+fn great_circle_distance_is_used() {
+    // Bologna Centrale to Milano Centrale is about 200 km
+    let distance = haversine_km((44.50626, 11.342267), (45.486347, 9.204528));
+    assert!((distance - 200.0).abs() < 5.0, "{}", distance);
+    assert_eq!(haversine_km((44.5, 11.3), (44.5, 11.3)), 0.0);
+}
+
+#[test]
+fn spatial_queries_agree_with_a_full_scan() {
+    let t = Trenitalia::new();
+    let point = (44.133333, 12.233333);
+    let mut expected: Vec<(&TrainStation, f64)> = t
+        .stations()
+        .iter()
+        .map(|station| (station, haversine_km(point, station.position)))
+        .collect();
+    expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let nearest = t.nearest_stations(point, 5);
+    assert_eq!(
+        nearest.iter().map(|(s, _)| &s.id).collect::<Vec<_>>(),
+        expected[..5].iter().map(|(s, _)| &s.id).collect::<Vec<_>>()
+    );
+    assert_eq!(t.nearest_station(point).id, nearest[0].0.id);
+    let within = t.stations_within(point, 20.0);
+    assert_eq!(
+        within.len(),
+        expected.iter().filter(|(_, d)| *d <= 20.0).count()
+    );
+    assert!(within.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    let in_box = t.stations_in_box((44.0, 12.0), (44.3, 12.5));
+    assert_eq!(
+        in_box.len(),
+        t.stations()
+            .iter()
+            .filter(
+                |s| (44.0..=44.3).contains(&s.position.0) && (12.0..=12.5).contains(&s.position.1)
+            )
+            .count()
+    );
+    // Far from any station the search still finds the closest ones
+    assert_eq!(t.nearest_stations((60.0, 30.0), 2).len(), 2);
+}
+
+#[test]
+fn station_database_can_be_queried() {
+    let t = Trenitalia::new();
+    assert_eq!(
+        t.stations().len(),
+        include_str!("../stations.tsv").lines().count()
+    );
+    assert_eq!(
+        t.get_train_station("BAC").unwrap().get_name(),
+        "Bologna Centrale"
+    );
+    assert!(t.get_train_station("QQQ").is_none());
+    assert_eq!(t.get_train_station_by_vt_id("S05043").unwrap().id, "BAC");
+    assert_eq!(
+        t.get_train_station_by_lefrecce_name("Bologna Centrale")
+            .unwrap()
+            .id,
+        "BAC"
+    );
+    let emilia_romagna: Vec<&TrainStation> = t.stations_by_region_id(8).collect();
+    assert!(emilia_romagna.iter().any(|station| station.id == "BAC"));
+    assert!(emilia_romagna.iter().all(|station| station.region_id == 8));
+}
+
+#[test]
+fn stations_have_a_region() {
+    use std::convert::TryFrom;
+    for region in Region::ALL.iter() {
+        assert_eq!(Region::try_from(region.id()).unwrap(), *region);
+    }
+    assert!(matches!(Region::try_from(0), Err(Error::UnknownRegion(0))));
+    assert!(Region::from_id(23).is_none());
+    assert_eq!(Region::ValleDAosta.name(), "Valle d'Aosta");
+    assert_eq!(Region::Toscana.english_name(), "Tuscany");
+    let t = offline_trenitalia();
+    assert!(t
+        .stations()
+        .iter()
+        .all(|station| station.region().is_some()));
+    let bologna = t.get_train_station("BAC").unwrap();
+    assert_eq!(bologna.region(), Some(Region::EmiliaRomagna));
+    assert!(t
+        .stations_in_region(Region::EmiliaRomagna)
+        .any(|station| station.id == "BAC"));
+    assert_eq!(t.station_region(bologna).unwrap(), Region::EmiliaRomagna);
+}
+
+#[test]
+fn station_database_can_be_loaded_at_runtime() {
+    let stations = "Bologna Centrale\tBAC\t8\t44.50626\t11.342267\nNuova\tNUO\t8\t44.6\t11.4\n";
+    let aliases = "Bologna C.le\tBAC\n";
+    let lefrecce_names = "BAC\tBOLOGNA CENTRALE\nNUO\tNUOVA";
+    let vt_ids = "BAC\tS05043\n";
+    let t = Trenitalia::from_readers(
+        stations.as_bytes(),
+        aliases.as_bytes(),
+        lefrecce_names.as_bytes(),
+        vt_ids.as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(t.stations().len(), 2);
+    assert_eq!(t.find_train_station("Bologna C.le").unwrap().id, "BAC");
+    assert_eq!(
+        t.get_train_station_by_lefrecce_name("NUOVA").unwrap().id,
+        "NUO"
+    );
+    assert!(t.get_train_station("NUO").unwrap().vt_id.is_none());
+
+    let error = Trenitalia::from_readers(
+        "Bologna Centrale\tBAC\t8\t44.50626\t11.342267\nNuova\tNUO\t8\tnord\t11.4".as_bytes(),
+        aliases.as_bytes(),
+        lefrecce_names.as_bytes(),
+        vt_ids.as_bytes(),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(error.to_string(), "stations.tsv:2: invalid latitude: nord");
+    let error = Trenitalia::from_readers(
+        stations.as_bytes(),
+        "Bologna C.le\tBAC\nAltra\tXXX\n".as_bytes(),
+        lefrecce_names.as_bytes(),
+        vt_ids.as_bytes(),
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(error, Error::StationData { line: Some(2), .. }));
+    let error =
+        Trenitalia::from_readers("\n".as_bytes(), "".as_bytes(), "".as_bytes(), "".as_bytes())
+            .err()
+            .unwrap();
+    assert_eq!(error.to_string(), "stations.tsv: no stations");
+    let error = Trenitalia::from_files("missing.tsv", "aliases.tsv", "id_lf_map.tsv", "id_vt.tsv")
+        .err()
+        .unwrap();
+    assert!(matches!(error, Error::StationData { line: None, .. }));
+    let t = Trenitalia::from_files("stations.tsv", "aliases.tsv", "id_lf_map.tsv", "id_vt.tsv")
+        .unwrap();
+    assert_eq!(t.stations().len(), Trenitalia::new().stations().len());
+}
+
+#[test]
+fn compiled_station_tables_match_the_tsv_files() {
+    let embedded = Trenitalia::new();
+    let parsed =
+        Trenitalia::from_files("stations.tsv", "aliases.tsv", "id_lf_map.tsv", "id_vt.tsv")
+            .unwrap();
+    assert_eq!(embedded.stations().len(), parsed.stations().len());
+    let id = |station: Option<&TrainStation>| station.map(|station| station.id.clone());
+    for (a, b) in embedded.stations().iter().zip(parsed.stations()) {
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.aliases, b.aliases);
+        assert_eq!(a.region_id, b.region_id);
+        assert_eq!(a.position, b.position);
+        assert_eq!(a.vt_id, b.vt_id);
+        assert_eq!(a.lefrecce_name, b.lefrecce_name);
+        assert_eq!(id(embedded.get_train_station(&a.id)), Some(a.id.clone()));
+        for alias in a.aliases.iter().chain(a.lefrecce_name.iter()) {
+            assert_eq!(
+                id(embedded.find_train_station(alias)),
+                id(parsed.find_train_station(alias))
+            );
+        }
+        if let Some(vt_id) = &a.vt_id {
+            assert_eq!(
+                id(embedded.get_train_station_by_vt_id(vt_id)),
+                id(parsed.get_train_station_by_vt_id(vt_id))
+            );
+        }
+        if let Some(name) = &a.lefrecce_name {
+            assert_eq!(
+                id(embedded.get_train_station_by_lefrecce_name(name)),
+                id(parsed.get_train_station_by_lefrecce_name(name))
+            );
+        }
+    }
+    // An empty LeFrecce name is no LeFrecce name
+    let barbusi = embedded.get_train_station("BSI").unwrap();
+    assert!(barbusi.lefrecce_name.is_none());
+    assert!(embedded.get_train_station_by_lefrecce_name("").is_none());
+    assert!(parsed.get_train_station_by_lefrecce_name("").is_none());
+}
+
+#[test]
+fn station_codes_are_encoded_in_five_bits_per_letter() {
+    let t = Trenitalia::new();
+    let code = t.get_train_station("BAC").unwrap().code().unwrap();
+    assert_eq!(code.encode(), 1 + (2 << 10));
+    assert_eq!(StationCode::decode(code.encode()).unwrap(), code);
+    assert_eq!(StationCode::decode(0).unwrap().as_str(), "AAA");
+    assert_eq!(
+        StationCode::decode(25 + (25 << 5) + (25 << 10))
+            .unwrap()
+            .as_str(),
+        "ZZZ"
+    );
+    assert!(StationCode::decode(26).is_err());
+    assert!(StationCode::decode(1 << 15).is_err());
+    for invalid in &["C(D", "bac", "BA", "BACC", "ÀBC"] {
+        assert!(matches!(
+            StationCode::parse(invalid),
+            Err(Error::InvalidStationCode(_))
+        ));
+    }
+}
+
+#[test]
+fn station_codes_are_generated_like_id_gen() {
+    let t = Trenitalia::new();
+    let mut generator = StationCodeGenerator::new();
+    let mut seen = std::collections::HashSet::new();
+    for line in include_str!("../stazioni_coord.tsv").lines().skip(1) {
+        let row: Vec<&str> = line.split('\t').collect();
+        if !seen.insert(row[1]) {
+            continue;
+        }
+        let code = generator.generate(row[0]).unwrap();
+        let expected = &t.get_train_station_by_vt_id(row[1]).unwrap().id;
+        // The script put the parenthesis of "Costigliole (Motta Di)" in its code
+        if expected == "C(D" {
+            assert_eq!(code.as_str(), "CMD");
+        } else {
+            assert_eq!(code.as_str(), expected, "{}", row[0]);
+        }
+    }
+    assert!(generator.is_used("BAC".parse().unwrap()));
+    let mut generator = StationCodeGenerator::with_used(vec!["BAC".parse().unwrap()]);
+    assert_ne!(
+        generator.generate("Bologna Centrale").unwrap().as_str(),
+        "BAC"
+    );
+    assert!(generator.generate("...").is_err());
+}
+
+#[test]
+fn station_database_is_compared_with_viaggiatreno() {
+    let t = offline_trenitalia();
+    let remote = t.station_list().unwrap();
+    assert_eq!(remote.len(), 5);
+    let diff = t.diff_stations(&remote, 0.5).unwrap();
+    let with_vt_id = t.stations().iter().filter(|s| s.vt_id.is_some()).count();
+    let removed = diff
+        .changes
+        .iter()
+        .filter(|change| matches!(change, StationChange::Removed(_)))
+        .count();
+    assert_eq!(removed, with_vt_id - 4);
+    assert_eq!(diff.stations.len(), t.stations().len() - removed + 1);
+    let mut changes = diff
+        .changes
+        .iter()
+        .filter(|change| !matches!(change, StationChange::Removed(_)));
+    match changes.next() {
+        Some(StationChange::Renamed { station, from }) => {
+            assert_eq!(station.id, "ZOO");
+            assert_eq!(station.get_name(), "Cesena Centrale");
+            assert_eq!(from, "Cesena");
+        }
+        change => panic!("{:?}", change),
+    }
+    match changes.next() {
+        Some(StationChange::Moved {
+            station,
+            distance_km,
+            ..
+        }) => {
+            assert_eq!(station.id, "RNI");
+            assert!((distance_km - 1.0).abs() < 0.1, "{}", distance_km);
+        }
+        change => panic!("{:?}", change),
+    }
+    let added = match changes.next() {
+        Some(StationChange::Added(station)) => station,
+        change => panic!("{:?}", change),
+    };
+    assert_eq!(added.get_name(), "Bologna Fiera");
+    assert!(added.code().is_ok());
+    assert!(t.get_train_station(&added.id).is_none());
+    assert!(changes.next().is_none());
+
+    let mut files: Vec<Vec<u8>> = vec![vec![]; 4];
+    if let [stations, aliases, lefrecce_names, vt_ids] = &mut files[..] {
+        diff.write(stations, aliases, lefrecce_names, vt_ids)
+            .unwrap();
+    }
+    let updated =
+        Trenitalia::from_readers(&files[0][..], &files[1][..], &files[2][..], &files[3][..])
+            .unwrap();
+    assert_eq!(
+        updated.get_train_station_by_vt_id("S05998").unwrap().id,
+        added.id
+    );
+    assert_eq!(updated.find_train_station("Cesena").unwrap().id, "ZOO");
+    assert_eq!(
+        updated.get_train_station("BAC").unwrap().get_name(),
+        "Bologna Centrale"
+    );
+}
+
+#[test]
+fn station_regions_and_abbreviations_are_compared() {
+    let t = Trenitalia::from_readers(
+        "Bologna Centrale\tBAC\t8\t44.50626\t11.342267\n".as_bytes(),
+        "Bologna C.le\tBAC\n".as_bytes(),
+        "".as_bytes(),
+        "BAC\tS05043\n".as_bytes(),
+    )
+    .unwrap();
+    let remote = vec![
+        RemoteStation {
+            vt_id: String::from("S05043"),
+            name: String::from("BOLOGNA C.LE"),
+            region_id: 9,
+            position: (44.50626, 11.342267),
+        },
+        RemoteStation {
+            vt_id: String::from("S05999"),
+            name: String::from("BOLOGNA P.TA S.VITALE"),
+            region_id: 8,
+            position: (44.4975, 11.3696),
+        },
+    ];
+    let diff = t.diff_stations(&remote, 0.5).unwrap();
+    match &diff.changes[..] {
+        [StationChange::RegionChanged { station, from }, StationChange::Added(added)] => {
+            assert_eq!(station.id, "BAC");
+            assert_eq!(station.region_id, 9);
+            assert_eq!(*from, 8);
+            assert_eq!(added.get_name(), "Bologna P.ta S.Vitale");
+        }
+        changes => panic!("{:?}", changes),
+    }
+    assert_eq!(diff.stations[0].region_id, 9);
+}
+
+#[test]
+fn unchanged_station_database_is_written_as_is() {
+    let t = Trenitalia::new();
+    let remote: Vec<RemoteStation> = t
+        .stations()
+        .iter()
+        .map(|station| RemoteStation {
+            vt_id: station.vt_id.clone().unwrap(),
+            name: station.get_name().to_uppercase(),
+            region_id: station.region_id,
+            position: station.position,
+        })
+        .collect();
+    let diff = t.diff_stations(&remote, 0.0).unwrap();
+    assert!(diff.changes.is_empty());
+    let (mut stations, mut aliases, mut lefrecce_names, mut vt_ids) =
+        (vec![], vec![], vec![], vec![]);
+    diff.write(
+        &mut stations,
+        &mut aliases,
+        &mut lefrecce_names,
+        &mut vt_ids,
+    )
+    .unwrap();
+    assert_eq!(stations, include_bytes!("../stations.tsv"));
+    // The stations without a LeFrecce name are left out
+    let expected_lefrecce_names: Vec<&str> = include_str!("../id_lf_map.tsv")
+        .lines()
+        .filter(|line| !line.ends_with('\t'))
+        .collect();
+    assert_eq!(
+        std::str::from_utf8(&lefrecce_names).unwrap(),
+        expected_lefrecce_names.join("\n")
+    );
+    assert_eq!(vt_ids, include_bytes!("../id_vt.tsv"));
+    let mut written: Vec<&str> = std::str::from_utf8(&aliases).unwrap().lines().collect();
+    let mut expected: Vec<&str> = include_str!("../aliases.tsv").lines().collect();
+    written.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(written, expected);
+}
+
+#[test]
+fn invalid_api_time_is_an_error() {
+    assert!(matches!(
+        utils::parse_rome_datetime("2020-13-45T25:00:00"),
+        Err(Error::TimeParse(_))
+    ));
+}
+
+#[test]
+fn api_times_are_italian_across_dst_changes() {
+    let summer = utils::parse_rome_datetime("2020-05-18T10:10:00").unwrap();
+    assert_eq!(summer.to_rfc3339(), "2020-05-18T10:10:00+02:00");
+    // The hour repeated when the clocks go back is read as summer time
+    let ambiguous = utils::parse_rome_datetime("2026-10-25T02:30:00").unwrap();
+    assert_eq!(ambiguous.to_rfc3339(), "2026-10-25T02:30:00+02:00");
+    // The hour skipped when the clocks go forward is moved one hour later
+    let skipped = utils::parse_rome_datetime("2026-03-29T02:30:00").unwrap();
+    assert_eq!(skipped.to_rfc3339(), "2026-03-29T03:30:00+02:00");
+    let millis = utils::datetime_from_millis(1589789400000).unwrap();
+    assert_eq!(millis, summer);
+}
+
+fn offline_trenitalia() -> Trenitalia {
+    Trenitalia::with_transport(offline_transport())
+}
+
+fn offline_transport() -> FixtureTransport {
+    let vt = "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/";
+    let lf = "https://www.lefrecce.it/msite/api/";
+    FixtureTransport::new()
+        .with_response(
+            &format!("{}soluzioniViaggioNew/5043/5066/", vt),
+            include_str!("../fixtures/vt_soluzioni_bologna_cesena.json"),
+        )
+        .with_response(
+            &format!("{}cercaNumeroTrenoTrenoAutocomplete/3905", vt),
+            include_str!("../fixtures/vt_cerca_numero_3905.txt"),
+        )
+        .with_response(
+            &format!("{}andamentoTreno/S05000/3905/1589752800000", vt),
+            include_str!("../fixtures/vt_andamento_treno_3905.json"),
+        )
+        .with_response(
+            &format!(
+                "{}solutions?origin=BOLOGNA%20CENTRALE&destination=CESENA&",
+                lf
+            ),
+            include_str!("../fixtures/lf_solutions_bologna_cesena.json"),
+        )
+        .with_response(
+            &format!("{}soluzioniViaggioNew/5058/5071/", vt),
+            include_str!("../fixtures/vt_soluzioni_empty.json"),
+        )
+        .with_response(
+            &format!("{}solutions?origin=FAENZA&destination=RIMINI&", lf),
+            include_str!("../fixtures/lf_solutions_faenza_rimini.json"),
+        )
+        .with_response(
+            &format!("{}partenze/S05043/", vt),
+            include_str!("../fixtures/vt_partenze_bologna.json"),
+        )
+        .with_response(
+            &format!("{}arrivi/S05043/", vt),
+            include_str!("../fixtures/vt_arrivi_bologna.json"),
+        )
+        .with_response(
+            &format!("{}cercaNumeroTrenoTrenoAutocomplete/6505", vt),
+            include_str!("../fixtures/vt_cerca_numero_6505.txt"),
+        )
+        .with_response(
+            &format!("{}andamentoTreno/S05043/6505/1589752800000", vt),
+            include_str!("../fixtures/vt_andamento_treno_6505.json"),
+        )
+        .with_response(
+            &format!("{}cercaNumeroTrenoTrenoAutocomplete/2123", vt),
+            include_str!("../fixtures/vt_cerca_numero_2123.txt"),
+        )
+        .with_response(
+            &format!("{}andamentoTreno/S05043/2123/1589752800000", vt),
+            include_str!("../fixtures/vt_andamento_treno_2123.json"),
+        )
+        .with_response(
+            &format!("{}soluzioniViaggioNew/5059/5071/", vt),
+            include_str!("../fixtures/vt_soluzioni_forli_rimini.json"),
+        )
+        .with_response(&format!("{}elencoStazioni/", vt), "[]")
+        .with_response(
+            &format!("{}elencoStazioni/8", vt),
+            include_str!("../fixtures/vt_elenco_stazioni_emilia_romagna.json"),
+        )
+        .with_response(
+            &format!("{}regione/S05043", vt),
+            include_str!("../fixtures/vt_regione_bologna.txt"),
+        )
+        .with_response(
+            &format!("{}solutions/x1f2e3d4c5b6a7f8e9d0c1b2a3f4e5d6c7/", lf),
+            include_str!("../fixtures/lf_details_faenza_rimini.json"),
+        )
+}
+
+#[test]
+fn can_find_trips_offline() {
+    let t = offline_trenitalia();
+    let bologna = t.get_train_station("BAC").unwrap();
+    let cesena = t.find_train_station("cesena").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let trips = t.find_trips(bologna, cesena, &when).unwrap();
+    assert_eq!(trips.len(), 2);
+    assert_eq!(u32::from(&trips[0].legs()[0].train_number), 3905);
+    assert_eq!(trips[1].legs()[0].arrival.0.id, cesena.id);
+}
+
+#[test]
+fn lefrecce_segments_are_resolved_to_stations() {
+    let t = offline_trenitalia();
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let trips = t.find_trips(faenza, rimini, &when).unwrap();
+    assert_eq!(trips.len(), 1);
+    let legs = trips[0].legs();
+    assert_eq!(legs.len(), 2);
+    assert_eq!(legs[0].departure.0.id, "FZA");
+    assert_eq!(legs[0].arrival.0.id, "FLL");
+    assert_eq!(legs[1].departure.0.id, "FLL");
+    assert_eq!(legs[1].arrival.0.id, "RNI");
+    assert_eq!(u32::from(&legs[1].train_number), 2123);
+}
+
+#[test]
+fn unknown_lefrecce_names_are_not_matched_fuzzily() {
+    let t = Trenitalia::new();
+    assert_eq!(t.station_from_lefrecce_name("Forli'").id, "FLL");
+    let lugano = t.station_from_lefrecce_name("LUGANO");
+    assert_eq!(lugano.id, "");
+    assert_eq!(lugano.get_name(), "LUGANO");
+    assert_eq!(lugano.lefrecce_name.as_deref(), Some("LUGANO"));
+}
+
+#[test]
+fn journey_summarises_its_legs() {
+    let t = offline_trenitalia();
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let journey = &t.find_trips(faenza, rimini, &when).unwrap()[0];
+    assert_eq!(journey.origin().id, "FZA");
+    assert_eq!(journey.destination().id, "RNI");
+    assert_eq!(journey.get_duration(), chrono::Duration::minutes(65));
+    assert_eq!(journey.changes(), 1);
+    assert_eq!(journey.price(), Some(7.2));
+    let transfers = journey.transfers();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].get_wait(), chrono::Duration::minutes(9));
+    assert!(!transfers[0].changes_station());
+}
+
+#[test]
+fn late_train_puts_the_connection_at_risk() {
+    let t = offline_trenitalia();
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let journey = &t.find_trips(faenza, rimini, &when).unwrap()[0];
+    let risks = journey
+        .connection_risks(&t, chrono::Duration::minutes(5))
+        .unwrap();
+    assert_eq!(risks.len(), 1);
+    assert_eq!(risks[0].status, ConnectionStatus::AtRisk);
+    assert_eq!(risks[0].buffer, chrono::Duration::minutes(3));
+    assert_eq!(
+        risks[0].expected_arrival.to_rfc3339(),
+        "2020-05-18T10:28:00+02:00"
+    );
+    let fallback = risks[0].fallback.as_ref().unwrap();
+    assert_eq!(u32::from(&fallback.legs()[0].train_number), 6507);
+    assert_eq!(fallback.destination().id, "RNI");
+    let risks = journey
+        .connection_risks(&t, chrono::Duration::minutes(2))
+        .unwrap();
+    assert_eq!(risks[0].status, ConnectionStatus::Safe);
+    assert!(risks[0].fallback.is_none());
+}
+
+#[test]
+fn journey_is_replanned_from_the_next_change() {
+    let t = offline_trenitalia();
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let journey = &t.find_trips(faenza, rimini, &when).unwrap()[0];
+    let now = utils::parse_rome_datetime("2020-05-18T10:15:00").unwrap();
+    let continuations = journey.replan(&t, &now).unwrap();
+    assert_eq!(continuations.len(), 2);
+    assert_eq!(continuations[0].origin().id, "FLL");
+    assert_eq!(u32::from(&continuations[1].legs()[0].train_number), 6507);
+    assert!(continuations
+        .iter()
+        .all(|continuation| continuation.destination().id == "RNI"));
+}
+
+#[test]
+fn journey_is_replanned_from_a_missed_change() {
+    // 6505 reached Forlì at 10:33, after 2123 had left at 10:31: these responses replace the
+    // ones of the offline transport
+    let vt = "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/";
+    let t = Trenitalia::with_transport(
+        offline_transport()
+            .with_response(
+                &format!("{}andamentoTreno/S05043/6505/1589752800000", vt),
+                include_str!("../fixtures/vt_andamento_treno_6505_forli.json"),
+            )
+            .with_response(
+                &format!("{}andamentoTreno/S05043/2123/1589752800000", vt),
+                include_str!("../fixtures/vt_andamento_treno_2123_forli.json"),
+            ),
+    );
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let journey = &t.find_trips(faenza, rimini, &when).unwrap()[0];
+    let now = utils::parse_rome_datetime("2020-05-18T10:35:00").unwrap();
+    let continuations = journey.replan(&t, &now).unwrap();
+    assert_eq!(continuations.len(), 1);
+    assert_eq!(continuations[0].origin().id, "FLL");
+    assert_eq!(u32::from(&continuations[0].legs()[0].train_number), 6507);
+}
+
+#[test]
+fn legs_without_live_status_are_assumed_on_time() {
+    let vt = "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/";
+    // Train 9999 is two trains, and neither of them calls at Cesena
+    let transport = std::sync::Arc::new(
+        offline_transport()
+            .with_response(
+                &format!("{}cercaNumeroTrenoTrenoAutocomplete/9999", vt),
+                "9999 - BOLOGNA CENTRALE|9999-S05043-1589752800000\n\
+                 9999 - ROMA TERMINI|9999-S08409-1589752800000",
+            )
+            .with_response(
+                &format!("{}andamentoTreno/S05043/9999/", vt),
+                include_str!("../fixtures/vt_andamento_treno_2123.json"),
+            )
+            .with_response(
+                &format!("{}andamentoTreno/S08409/9999/", vt),
+                include_str!("../fixtures/vt_andamento_treno_2123.json"),
+            ),
+    );
+    let t = Trenitalia::with_transport(transport.clone());
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let cesena = t.find_train_station("cesena").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let first = t.find_trips(faenza, rimini, &when).unwrap()[0].legs()[0].clone();
+    let departure = utils::parse_rome_datetime("2020-05-18T10:40:00").unwrap();
+    let arrival = utils::parse_rome_datetime("2020-05-18T11:20:00").unwrap();
+    for (train_number, from) in [
+        (TrainNumber::Bus { number: 8888 }, &first.arrival.0),
+        (TrainNumber::RegionaleVeloce { number: 9999 }, cesena),
+    ] {
+        let journey = Journey::new(
+            vec![
+                first.clone(),
+                TrainTrip {
+                    train_number,
+                    departure: (from.clone(), departure),
+                    arrival: (rimini.clone(), arrival),
+                },
+            ],
+            None,
+        )
+        .unwrap();
+        let risks = journey
+            .connection_risks(&t, chrono::Duration::minutes(5))
+            .unwrap();
+        assert_eq!(risks[0].status, ConnectionStatus::Safe);
+        assert_eq!(risks[0].expected_departure, departure);
+    }
+    assert!(!transport.requests().iter().any(|url| url.contains("/8888")));
+}
+
+#[test]
+fn can_obtain_train_info_offline() {
+    let t = offline_trenitalia();
+    let info = t.train_info(3905, "Piacenza".to_string()).unwrap();
+    assert_eq!(info.stops.len(), 5);
+    assert_eq!(info.current_station.get_name(), "Fidenza");
+    assert_eq!(info.current_delay, 3);
+    assert!(!info.is_at_station);
+    assert_eq!(info.train_number.to_string(), "RV3905");
+    assert_eq!(info.origin.get_name(), "Piacenza");
+    assert_eq!(info.destination.id, "BAC");
+    assert_eq!(info.last_detection_station.as_deref(), Some("FIDENZA"));
+    assert_eq!(
+        info.last_detection_time.unwrap().to_rfc3339(),
+        "2020-05-18T06:24:00+02:00"
+    );
+    assert_eq!(info.measure, TrainMeasure::None);
+    assert!(!info.rescheduled);
+    assert_eq!(
+        info.sub_title.as_deref(),
+        Some("Il treno viaggia con 3 minuti di ritardo")
+    );
+}
+
+#[test]
+fn train_info_stations_are_found_by_viaggiatreno_id() {
+    let t = offline_trenitalia();
+    let mut status: serde_json::Value =
+        serde_json::from_str(include_str!("../fixtures/vt_andamento_treno_3905.json")).unwrap();
+    // A name that doesn't match the database, and a terminus abroad
+    status["fermate"][2]["stazione"] = "PARMA NUOVA".into();
+    status["fermate"][4]["id"] = "S11111".into();
+    status["fermate"][4]["stazione"] = "MUENCHEN HBF".into();
+    status["idDestinazione"] = "S11111".into();
+    status["destinazione"] = "MUENCHEN HBF".into();
+    let status: mapping::VTTrainStatus = serde_json::from_value(status).unwrap();
+    let info = TrainInfo::from(&status, &t).unwrap();
+    assert_eq!(
+        info.stops[2].station.id,
+        t.get_train_station_by_vt_id("S05014").unwrap().id
+    );
+    assert_eq!(info.origin.vt_id.as_deref(), Some("S05000"));
+    assert_eq!(info.destination.id, "");
+    assert_eq!(info.destination.get_name(), "MUENCHEN HBF");
+    assert_eq!(info.destination.vt_id.as_deref(), Some("S11111"));
+    assert_eq!(info.stops[4].station.id, "");
+}
+
+#[test]
+fn stops_report_delay_and_kind() {
+    let t = offline_trenitalia();
+    let info = t.train_info(3905, "Piacenza".to_string()).unwrap();
+    let kinds: Vec<StopKind> = info.stops.iter().map(|stop| stop.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            StopKind::Origin,
+            StopKind::Intermediate,
+            StopKind::Intermediate,
+            StopKind::Cancelled,
+            StopKind::Terminus
+        ]
+    );
+    assert_eq!(info.stops[0].departure_delay, 2);
+    assert_eq!(info.stops[1].arrival_delay, 3);
+    assert!(info.stops[2].next_stop_changed);
+    assert!(info.stops[3].is_cancelled());
+    assert!(!info.stops[4].is_cancelled());
+}
+
+#[test]
+fn platform_changes_are_detected() {
+    let t = offline_trenitalia();
+    let info = t.train_info(3905, "Piacenza".to_string()).unwrap();
+    let fidenza = &info.stops[1];
+    assert_eq!(fidenza.scheduled_arrival_platform.as_deref(), Some("2"));
+    assert_eq!(fidenza.actual_arrival_platform, None);
+    assert_eq!(fidenza.platform(), Some("2"));
+    assert!(!fidenza.platform_changed());
+    let parma = &info.stops[2];
+    assert_eq!(parma.scheduled_arrival_platform.as_deref(), Some("4"));
+    assert_eq!(parma.actual_arrival_platform.as_deref(), Some("5"));
+    assert!(parma.platform_changed());
+    assert_eq!(info.stops[4].platform(), Some("6"));
+}
+
+/// Clock that doesn't wait, but moves forward when asked to sleep
+struct TestClock {
+    now: std::sync::Mutex<chrono::DateTime<chrono_tz::Tz>>,
+    sleeps: std::sync::Mutex<Vec<std::time::Duration>>,
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> chrono::DateTime<chrono_tz::Tz> {
+        *self.now.lock().unwrap()
+    }
+    fn sleep(&self, duration: std::time::Duration) {
+        *self.now.lock().unwrap() += chrono::Duration::from_std(duration).unwrap();
+        self.sleeps.lock().unwrap().push(duration);
+    }
+}
+
+#[test]
+fn watcher_reports_changes_between_polls() {
+    let base: serde_json::Value =
+        serde_json::from_str(include_str!("../fixtures/vt_andamento_treno_3905.json")).unwrap();
+    // Before Parma: scheduled platform and no cancelled stops
+    let mut first = base.clone();
+    first["fermate"][2]["binarioEffettivoArrivoDescrizione"] = serde_json::Value::Null;
+    first["fermate"][3]["actualFermataType"] = 1.into();
+    // At Parma, five minutes late, on another platform and with Reggio Emilia cancelled
+    let mut second = base.clone();
+    second["fermate"][2]["arrivoReale"] = 1589776800000u64.into();
+    second["ritardo"] = 5.into();
+    // At Bologna Centrale
+    let mut third = second.clone();
+    third["fermate"][4]["arrivoReale"] = 1589780400000u64.into();
+    let vt = "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/";
+    let t = Trenitalia::with_transport(
+        FixtureTransport::new()
+            .with_response(
+                &format!("{}cercaNumeroTrenoTrenoAutocomplete/3905", vt),
+                include_str!("../fixtures/vt_cerca_numero_3905.txt"),
+            )
+            .with_responses(
+                &format!("{}andamentoTreno/S05000/3905/", vt),
+                &[&first.to_string(), &second.to_string(), &third.to_string()],
+            ),
+    );
+    let clock = std::sync::Arc::new(TestClock {
+        now: std::sync::Mutex::new(utils::parse_rome_datetime("2020-05-18T06:25:00").unwrap()),
+        sleeps: std::sync::Mutex::new(vec![]),
+    });
+    let watcher = TrainWatcher::new(
+        &t,
+        3905,
+        WatchedTrain::From("Piacenza".to_string()),
+        std::time::Duration::from_secs(60),
+    )
+    .with_clock(clock.clone());
+    let events: Vec<TrainEvent> = watcher.map(Result::unwrap).collect();
+    let names: Vec<String> = events
+        .iter()
+        .map(|event| match event {
+            TrainEvent::Arrived { station, .. } => format!("arrived {}", station.id),
+            TrainEvent::Departed { station, .. } => format!("departed {}", station.id),
+            TrainEvent::PlatformChanged {
+                station,
+                scheduled,
+                actual,
+            } => format!("platform {} {}->{}", station.id, scheduled, actual),
+            TrainEvent::StopCancelled { station } => format!("cancelled {}", station.id),
+            TrainEvent::DelayChanged { from, to } => format!("delay {}->{}", from, to),
+        })
+        .collect();
+    let parma = t.find_train_station("parma").unwrap();
+    let reggio = t.find_train_station("reggio emilia").unwrap();
+    assert_eq!(
+        names,
+        vec![
+            format!("arrived {}", parma.id),
+            format!("platform {} 4->5", parma.id),
+            format!("cancelled {}", reggio.id),
+            "delay 3->5".to_string(),
+            "arrived BAC".to_string(),
+        ]
+    );
+    assert_eq!(
+        *clock.sleeps.lock().unwrap(),
+        vec![std::time::Duration::from_secs(60); 2]
+    );
+}
+
+#[test]
+fn watcher_follows_stops_abroad_and_trains_ending_early() {
+    let mut base: serde_json::Value =
+        serde_json::from_str(include_str!("../fixtures/vt_andamento_treno_3905.json")).unwrap();
+    // Two stops abroad, and the train ends in Reggio Emilia instead of Bologna Centrale
+    base["fermate"][1]["id"] = "S99001".into();
+    base["fermate"][1]["stazione"] = "LUGANO".into();
+    base["fermate"][2]["id"] = "S99002".into();
+    base["fermate"][2]["stazione"] = "BELLINZONA".into();
+    base["fermate"][2]["binarioEffettivoArrivoDescrizione"] = serde_json::Value::Null;
+    base["fermate"][3]["actualFermataType"] = 1.into();
+    base["fermate"][4]["actualFermataType"] = 3.into();
+    let mut second = base.clone();
+    second["fermate"][2]["arrivoReale"] = 1589776800000u64.into();
+    let mut third = second.clone();
+    third["fermate"][3]["arrivoReale"] = 1589778000000u64.into();
+    let vt = "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/";
+    let t = Trenitalia::with_transport(
+        FixtureTransport::new()
+            .with_response(
+                &format!("{}cercaNumeroTrenoTrenoAutocomplete/3905", vt),
+                include_str!("../fixtures/vt_cerca_numero_3905.txt"),
+            )
+            .with_responses(
+                &format!("{}andamentoTreno/S05000/3905/", vt),
+                &[&base.to_string(), &second.to_string(), &third.to_string()],
+            ),
+    );
+    let mut watcher = TrainWatcher::new(
+        &t,
+        3905,
+        WatchedTrain::From("Piacenza".to_string()),
+        std::time::Duration::from_secs(60),
+    );
+    assert!(watcher.poll().unwrap().is_empty());
+    match &watcher.poll().unwrap()[..] {
+        [TrainEvent::Arrived { station, .. }] => assert_eq!(station.get_name(), "BELLINZONA"),
+        events => panic!("{:?}", events),
+    }
+    assert!(!watcher.is_finished());
+    match &watcher.poll().unwrap()[..] {
+        [TrainEvent::Arrived { station, .. }] => assert_eq!(station.id, "ROE"),
+        events => panic!("{:?}", events),
+    }
+    assert!(watcher.is_finished());
+}
+
+#[test]
+fn train_search_results_carry_the_departure_date() {
+    let candidates = viaggiatreno::parse_train_candidates(
+        "3905 - PIACENZA|3905-S05000-1589752800000\n2 - ROMA TERMINI|2-S08409",
+    )
+    .unwrap();
+    assert_eq!(candidates[0].station_code, "S05000");
+    assert_eq!(candidates[0].date, Some(1589752800000));
+    assert_eq!(candidates[1].origin, "ROMA TERMINI");
+    assert_eq!(candidates[1].date, None);
+}
+
+#[test]
+fn can_get_fare_offline() {
+    let t = offline_trenitalia();
+    let trip = TrainTrip {
+        train_number: TrainNumber::RegionaleVeloce { number: 3905 },
+        departure: (
+            t.get_train_station("BAC").unwrap().clone(),
+            utils::datetime_from_millis(1589789400000).unwrap(),
+        ),
+        arrival: (
+            t.find_train_station("cesena").unwrap().clone(),
+            utils::datetime_from_millis(1589793300000).unwrap(),
+        ),
+    };
+    assert_eq!(trip.get_fare(&t).unwrap(), Some(9.85));
+}
+
+#[test]
+fn can_read_station_boards_offline() {
+    let t = offline_trenitalia();
+    let bologna = t.get_train_station("BAC").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let departures = t.departures(bologna, &when).unwrap();
+    assert_eq!(departures.len(), 2);
+    assert_eq!(departures[0].train_number.to_string(), "RV3905");
+    assert_eq!(departures[0].station.as_ref().unwrap().get_name(), "Cesena");
+    assert_eq!(
+        departures[0].scheduled_time.to_rfc3339(),
+        "2020-05-18T10:10:00+02:00"
+    );
+    assert_eq!(
+        departures[0].expected_time().to_rfc3339(),
+        "2020-05-18T10:13:00+02:00"
+    );
+    assert_eq!(departures[0].scheduled_platform.as_deref(), Some("5"));
+    assert_eq!(departures[0].actual_platform.as_deref(), Some("6"));
+    assert_eq!(departures[1].station_name, "LECCE");
+    assert_eq!(departures[1].actual_platform, None);
+    let arrivals = t.arrivals(bologna, &when).unwrap();
+    assert_eq!(arrivals[0].station.as_ref().unwrap().get_name(), "Piacenza");
+    assert_eq!(
+        arrivals[0].scheduled_time.to_rfc3339(),
+        "2020-05-18T10:05:00+02:00"
+    );
+    assert!(
+        viaggiatreno::board_url(&t, viaggiatreno::Board::Departures, bologna, &when)
+            .unwrap()
+            .ends_with("/partenze/S05043/Mon%20May%2018%202020%2010:00:00%20GMT%2B0200")
+    );
+    // Unknown termini are not matched to a similar station
+    let board = r#"[
+        {"numeroTreno": 1288, "destinazione": "MUENCHEN HBF", "origine": "VERONA PORTA NUOVA",
+         "codOrigine": "S05059", "orarioPartenza": 1589789400000, "orarioArrivo": 1589789400000},
+        {"numeroTreno": 2123, "destinazione": "BOLOGNA C.LE", "origine": "PIACENZ",
+         "orarioPartenza": 1589789400000, "orarioArrivo": 1589789400000}
+    ]"#;
+    let unknown_departures =
+        viaggiatreno::parse_board(&t, viaggiatreno::Board::Departures, board).unwrap();
+    assert!(unknown_departures[0].station.is_none());
+    assert_eq!(unknown_departures[0].station_name, "MUENCHEN HBF");
+    assert_eq!(unknown_departures[1].station.as_ref().unwrap().id, "BAC");
+    let unknown_arrivals =
+        viaggiatreno::parse_board(&t, viaggiatreno::Board::Arrivals, board).unwrap();
+    assert_eq!(unknown_arrivals[0].station.as_ref().unwrap().id, "FLL");
+    assert!(unknown_arrivals[1].station.is_none());
+}
+
+#[test]
+fn fixture_responses_are_matched_by_longest_prefix() {
+    let transport = FixtureTransport::new()
+        .with_response("http://example.com/", "short")
+        .with_response("http://example.com/a", "old")
+        .with_response("http://example.com/a", "long");
+    assert_eq!(
+        Transport::get(&transport, "http://example.com/ab").unwrap(),
+        "long"
+    );
+    assert_eq!(
+        Transport::get(&transport, "http://example.com/b").unwrap(),
+        "short"
+    );
+    assert!(matches!(
+        Transport::get(&transport, "http://example.org/"),
+        Err(Error::HttpStatus { status: 404, .. })
+    ));
+}
+
+#[test]
+fn search_times_are_sent_as_italian_time() {
+    let transport = std::sync::Arc::new(FixtureTransport::new());
+    let t = Trenitalia::with_transport(transport.clone());
+    let bologna = t.get_train_station("BAC").unwrap();
+    let rimini = t.get_train_station("RNI").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00")
+        .unwrap()
+        .with_timezone(&chrono_tz::UTC);
+    let _ = t.find_trips(bologna, rimini, &when);
+    let trip = TrainTrip {
+        train_number: TrainNumber::RegionaleVeloce { number: 2123 },
+        departure: (bologna.clone(), when),
+        arrival: (rimini.clone(), when + chrono::Duration::hours(2)),
+    };
+    let _ = trip.get_fare(&t);
+    let requests = transport.requests();
+    assert!(requests[0].ends_with("soluzioniViaggioNew/5043/5071/2020-05-18T10:00:00"));
+    let fare_request = requests.last().unwrap();
+    assert!(fare_request.contains("adate=18/05/2020&atime=10&"));
+}
+
+#[test]
+fn custom_base_urls_are_used() {
+    let transport = std::sync::Arc::new(FixtureTransport::new().with_response(
+        "http://localhost:8080/lefrecce/solutions?",
+        include_str!("../fixtures/lf_solutions_bologna_cesena.json"),
+    ));
+    let t = Trenitalia::builder()
+        .transport(transport.clone())
+        .viaggiatreno_base_url("http://localhost:8080/viaggiatreno/")
+        .lefrecce_base_url("http://localhost:8080/lefrecce")
+        .build();
+    let cesena = t.find_train_station("cesena").unwrap();
+    assert!(t
+        .find_trips(cesena, cesena, &chrono::Utc::now().with_timezone(&TIMEZONE))
+        .is_err());
+    assert!(t.find_train_station_online("cesena").is_err());
+    let trip = TrainTrip {
+        train_number: TrainNumber::RegionaleVeloce { number: 3905 },
+        departure: (
+            t.get_train_station("BAC").unwrap().clone(),
+            utils::datetime_from_millis(1589789400000).unwrap(),
+        ),
+        arrival: (
+            cesena.clone(),
+            utils::datetime_from_millis(1589793300000).unwrap(),
+        ),
+    };
+    assert_eq!(trip.get_fare(&t).unwrap(), Some(9.85));
+    let requests = transport.requests();
+    assert!(requests[0].starts_with("http://localhost:8080/viaggiatreno/soluzioniViaggioNew/"));
+    assert_eq!(
+        requests[1],
+        "http://localhost:8080/viaggiatreno/autocompletaStazione/cesena"
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_api_matches_blocking_api() {
+    let t = offline_trenitalia();
+    let bologna = t.get_train_station("BAC").unwrap();
+    let cesena = t.find_train_station("cesena").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let trips = t.find_trips_async(bologna, cesena, &when).await.unwrap();
+    assert_eq!(trips.len(), 2);
+    assert_eq!(
+        trips[0].legs()[0].get_fare_async(&t).await.unwrap(),
+        Some(9.85)
+    );
+    let info = t.train_info_calling_at_async(3905, cesena).await.unwrap();
+    assert_eq!(info.current_delay, 3);
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let journey = &t.find_trips_async(faenza, rimini, &when).await.unwrap()[0];
+    let risks = journey
+        .connection_risks_async(&t, chrono::Duration::minutes(5))
+        .await
+        .unwrap();
+    assert_eq!(risks[0].status, ConnectionStatus::AtRisk);
+    assert!(risks[0].fallback.is_some());
+    let now = utils::parse_rome_datetime("2020-05-18T10:15:00").unwrap();
+    assert_eq!(journey.replan_async(&t, &now).await.unwrap().len(), 2);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn domain_types_round_trip_through_serde() {
+    let number = TrainNumber::FrecciaRossa { number: 9515 };
+    let json = serde_json::to_value(&number).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"type": "FrecciaRossa", "number": 9515})
+    );
+    let t = offline_trenitalia();
+    let faenza = t.find_train_station("faenza").unwrap();
+    let rimini = t.find_train_station("rimini").unwrap();
+    let when = utils::parse_rome_datetime("2020-05-18T10:00:00").unwrap();
+    let journey = &t.find_trips(faenza, rimini, &when).unwrap()[0];
+    let decoded: Journey = serde_json::from_str(&serde_json::to_string(journey).unwrap()).unwrap();
+    assert_eq!(decoded.legs().len(), 2);
+    assert_eq!(decoded.departure_time(), journey.departure_time());
+    assert_eq!(decoded.price(), Some(7.2));
+    assert!(serde_json::from_str::<Journey>(r#"{"legs":[],"price":null}"#).is_err());
+    let info = t.train_info(3905, "Piacenza".to_string()).unwrap();
+    let decoded: TrainInfo = serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+    assert_eq!(decoded.stops.len(), info.stops.len());
+}
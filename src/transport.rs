@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::Error;
+
+/// HTTP client used to reach the ViaggiaTreno and LeFrecce APIs
+pub trait Transport: Send + Sync {
+    /// Performs a GET request and returns the response body
+    fn get(&self, url: &str) -> Result<String, Error>;
+}
+
+/// Default transport, which performs real HTTP requests with ureq
+pub struct UreqTransport {
+    agent: ureq::Agent,
+}
+
+impl UreqTransport {
+    pub fn new() -> Self {
+        UreqTransport {
+            agent: ureq::agent(),
+        }
+    }
+}
+
+impl Default for UreqTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for UreqTransport {
+    fn get(&self, url: &str) -> Result<String, Error> {
+        Ok(self.agent.get(url).call()?.into_string()?)
+    }
+}
+
+/// Transport that answers with recorded responses, without accessing the network
+///
+/// Each response is registered for a URL prefix, replacing the one already registered for the
+/// same prefix; when more than one prefix matches a request the longest one wins. Requests that
+/// don't match any prefix fail with HTTP 404.
+#[derive(Default)]
+pub struct FixtureTransport {
+    responses: Vec<(String, Vec<String>, AtomicUsize)>,
+    requests: std::sync::Mutex<Vec<String>>,
+}
+
+impl FixtureTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every request whose URL starts with `url_prefix` with `body`
+    pub fn with_response(self, url_prefix: &str, body: &str) -> Self {
+        self.with_responses(url_prefix, &[body])
+    }
+
+    /// Answers the requests whose URL starts with `url_prefix` with `bodies`, one per request.
+    /// Once all of them have been used, the last one is repeated.
+    pub fn with_responses(mut self, url_prefix: &str, bodies: &[&str]) -> Self {
+        self.responses.retain(|(prefix, _, _)| prefix != url_prefix);
+        self.responses.push((
+            url_prefix.to_string(),
+            bodies.iter().map(|body| body.to_string()).collect(),
+            AtomicUsize::new(0),
+        ));
+        self
+    }
+
+    /// Returns the URLs requested so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for FixtureTransport {
+    fn get(&self, url: &str) -> Result<String, Error> {
+        self.requests.lock().unwrap().push(url.to_string());
+        self.responses
+            .iter()
+            .filter(|(prefix, _, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _, _)| prefix.len())
+            .and_then(|(_, bodies, served)| {
+                let index = served.fetch_add(1, Ordering::SeqCst);
+                bodies.get(index).or_else(|| bodies.last()).cloned()
+            })
+            .ok_or_else(|| Error::HttpStatus {
+                url: url.to_string(),
+                status: 404,
+            })
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn get(&self, url: &str) -> Result<String, Error> {
+        (**self).get(url)
+    }
+}
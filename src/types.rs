@@ -1,10 +1,14 @@
-use chrono::TimeZone;
 use serde::{Deserialize, Serialize};
 
-use crate::{mapping, Trenitalia};
+use crate::{mapping, utils, viaggiatreno, Error, Trenitalia};
 
 /// Train type and number representation
+///
+/// With the `serde` feature it's represented as an object tagged by the train type,
+/// e.g. `{"type": "FrecciaRossa", "number": 9515}` or
+/// `{"type": "Unknown", "number": 123, "name": "XYZ"}`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(tag = "type"))]
 pub enum TrainNumber {
     Regionale {
         number: u32,
@@ -43,9 +47,10 @@ pub enum TrainNumber {
         name: String,
     },
 }
-impl std::string::ToString for TrainNumber {
-    fn to_string(&self) -> String {
-        format!(
+impl std::fmt::Display for TrainNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
             "{}{}",
             match self {
                 Self::Regionale { number: _ } => "R",
@@ -84,27 +89,108 @@ impl std::convert::From<&TrainNumber> for u32 {
 
 /// A specific stop in a train trip
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TrainTripStop {
     pub station: TrainStation,
     pub platform: String,
-    pub arrival: Option<chrono::DateTime<chrono::Local>>,
-    pub departure: Option<chrono::DateTime<chrono::Local>>,
-    pub expected_arrival: Option<chrono::DateTime<chrono::Local>>,
-    pub expected_departure: Option<chrono::DateTime<chrono::Local>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub arrival: Option<chrono::DateTime<chrono_tz::Tz>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub departure: Option<chrono::DateTime<chrono_tz::Tz>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub expected_arrival: Option<chrono::DateTime<chrono_tz::Tz>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub expected_departure: Option<chrono::DateTime<chrono_tz::Tz>>,
+}
+
+/// Role of a stop in the train's route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StopKind {
+    /// The station the train starts from
+    Origin,
+    /// A scheduled stop along the way
+    Intermediate,
+    /// The station the train ends in
+    Terminus,
+    /// A scheduled stop that the train skips
+    Cancelled,
+    /// A stop that wasn't scheduled
+    Extraordinary,
+}
+
+impl StopKind {
+    /// Converts the ViaggiaTreno `tipoFermata` and `actualFermataType` fields
+    pub(crate) fn from_api(stop_type: char, actual_type: i8) -> Self {
+        match (actual_type, stop_type) {
+            (3, _) => Self::Cancelled,
+            (2, _) => Self::Extraordinary,
+            (_, 'P') => Self::Origin,
+            (_, 'A') => Self::Terminus,
+            _ => Self::Intermediate,
+        }
+    }
 }
 
 /// A specific stop in a train trip
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DetailedTrainTripStop {
     pub station: TrainStation,
-    pub platform: String,
-    pub arrival: Option<chrono::DateTime<chrono::Local>>,
-    pub departure: Option<chrono::DateTime<chrono::Local>>,
-    pub expected_arrival: Option<chrono::DateTime<chrono::Local>>,
-    pub expected_departure: Option<chrono::DateTime<chrono::Local>>,
+    pub scheduled_arrival_platform: Option<String>,
+    pub actual_arrival_platform: Option<String>,
+    pub scheduled_departure_platform: Option<String>,
+    pub actual_departure_platform: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub arrival: Option<chrono::DateTime<chrono_tz::Tz>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub departure: Option<chrono::DateTime<chrono_tz::Tz>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub expected_arrival: Option<chrono::DateTime<chrono_tz::Tz>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub expected_departure: Option<chrono::DateTime<chrono_tz::Tz>>,
+    /// Arrival delay in minutes
+    pub arrival_delay: i16,
+    /// Departure delay in minutes
+    pub departure_delay: i16,
+    pub kind: StopKind,
+    /// Whether the stop following this one has changed
+    pub next_stop_changed: bool,
+}
+
+impl DetailedTrainTripStop {
+    /// Whether the train skips this stop
+    pub fn is_cancelled(&self) -> bool {
+        self.kind == StopKind::Cancelled
+    }
+    /// The platform the train leaves from (or arrives at, at its terminus), if known
+    pub fn platform(&self) -> Option<&str> {
+        self.actual_departure_platform
+            .as_ref()
+            .or(self.scheduled_departure_platform.as_ref())
+            .or(self.actual_arrival_platform.as_ref())
+            .or(self.scheduled_arrival_platform.as_ref())
+            .map(String::as_str)
+    }
+    /// Whether the train arrives at or leaves from a platform other than the scheduled one
+    pub fn platform_changed(&self) -> bool {
+        let changed =
+            |scheduled: &Option<String>, actual: &Option<String>| match (scheduled, actual) {
+                (Some(scheduled), Some(actual)) => scheduled != actual,
+                _ => false,
+            };
+        changed(
+            &self.scheduled_arrival_platform,
+            &self.actual_arrival_platform,
+        ) || changed(
+            &self.scheduled_departure_platform,
+            &self.actual_departure_platform,
+        )
+    }
 }
 
 /// A train trip with stops specified
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DetailedTrainTrip {
     pub from: TrainStation,
     pub to: TrainStation,
@@ -113,139 +199,398 @@ pub struct DetailedTrainTrip {
 }
 
 /// A train trip between two stations. Stops aren't specified
+///
+/// With the `serde` feature the departure and the arrival are represented as
+/// `[station, time]` pairs, where the time is an RFC 3339 string.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TrainTrip {
     pub train_number: TrainNumber,
     /// Specify the station and time of arrival
-    pub arrival: (TrainStation, chrono::DateTime<chrono::Local>),
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::rome_datetime::station")
+    )]
+    pub arrival: (TrainStation, chrono::DateTime<chrono_tz::Tz>),
     /// Specify the station and time of departure
-    pub departure: (TrainStation, chrono::DateTime<chrono::Local>),
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::rome_datetime::station")
+    )]
+    pub departure: (TrainStation, chrono::DateTime<chrono_tz::Tz>),
 }
 
 impl TrainTrip {
     /// This method returns the trip's duration
     pub fn get_duration(&self) -> chrono::Duration {
-        let partenza = (&self.departure.1).clone();
-        let arrivo = (&self.arrival.1).clone();
+        let partenza = self.departure.1;
+        let arrivo = self.arrival.1;
         arrivo.signed_duration_since(partenza)
     }
     /// This method returns the trip's fare
-    pub fn get_fare(&self) -> Option<f64> {
-        let url = format!("https://www.lefrecce.it/msite/api/solutions?origin={}&destination={}&arflag=A&adate={}&atime={}&adultno=1&childno=0&direction=A&frecce=false&onlyRegional=false",
-            self.departure.0.lefrecce_name.clone().unwrap().replace(" ", "%20"),
-            self.arrival.0.lefrecce_name.clone().unwrap().replace(" ", "%20"),
-            self.departure.1.format("%d/%m/%Y"),
-            self.departure.1.format("%H")
-        );
-        let answer = ureq::get(url.as_str())
-            .call()
-            .unwrap()
-            .into_string()
-            .unwrap();
-        let body: Vec<crate::lefrecce::LFSolution> = serde_json::from_str(&answer).unwrap();
-        for result in body {
-            if chrono::Local.timestamp_millis(result.departuretime as i64) == self.departure.1
-                && chrono::Local.timestamp_millis(result.arrivaltime as i64) == self.arrival.1
+    pub fn get_fare(&self, trenitalia: &Trenitalia) -> Result<Option<f64>, Error> {
+        let (from_name, to_name) = self.lefrecce_names()?;
+        let body =
+            crate::lefrecce::search_solutions(trenitalia, from_name, to_name, &self.departure.1)?;
+        self.fare_from_solutions(&body)
+    }
+
+    /// Returns the LeFrecce names of the departure and arrival stations
+    pub(crate) fn lefrecce_names(&self) -> Result<(&str, &str), Error> {
+        match (
+            &self.departure.0.lefrecce_name,
+            &self.arrival.0.lefrecce_name,
+        ) {
+            (Some(from_name), Some(to_name)) => Ok((from_name, to_name)),
+            (None, _) => Err(Error::UnknownStation(
+                self.departure.0.get_name().to_string(),
+            )),
+            (_, None) => Err(Error::UnknownStation(self.arrival.0.get_name().to_string())),
+        }
+    }
+
+    /// Returns the price of the LeFrecce solution with the same times as this trip
+    pub(crate) fn fare_from_solutions(
+        &self,
+        solutions: &[crate::lefrecce::LFSolution],
+    ) -> Result<Option<f64>, Error> {
+        for result in solutions {
+            if utils::datetime_from_millis(result.departuretime as i64)? == self.departure.1
+                && utils::datetime_from_millis(result.arrivaltime as i64)? == self.arrival.1
             {
-                return result.minprice;
+                return Ok(result.minprice);
             }
         }
-        None
+        Ok(None)
+    }
+}
+
+/// A train leaving from or arriving at a station, as listed on the station board
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BoardEntry {
+    pub train_number: TrainNumber,
+    /// Destination of a departing train or origin of an arriving one, if it's a known station
+    pub station: Option<TrainStation>,
+    /// Name of the destination or of the origin as returned by ViaggiaTreno
+    pub station_name: String,
+    /// Scheduled departure or arrival time
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime"))]
+    pub scheduled_time: chrono::DateTime<chrono_tz::Tz>,
+    /// Delay in minutes
+    pub delay: i16,
+    pub scheduled_platform: Option<String>,
+    pub actual_platform: Option<String>,
+}
+
+impl BoardEntry {
+    /// This method returns the scheduled time plus the delay
+    pub fn expected_time(&self) -> chrono::DateTime<chrono_tz::Tz> {
+        self.scheduled_time + chrono::Duration::minutes(self.delay as i64)
+    }
+}
+
+/// Measure taken on a train by the railway operator (the ViaggiaTreno "provvedimento")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TrainMeasure {
+    /// The train runs as scheduled
+    None,
+    /// The whole train has been cancelled
+    Cancelled,
+    /// Some of the stops have been cancelled
+    PartiallyCancelled,
+    /// The train runs on a different route
+    Rerouted,
+    /// A measure not known to this crate, with its ViaggiaTreno code
+    Other(i8),
+}
+
+impl std::convert::From<i8> for TrainMeasure {
+    fn from(code: i8) -> Self {
+        match code {
+            0 => Self::None,
+            1 => Self::Cancelled,
+            2 => Self::PartiallyCancelled,
+            3 => Self::Rerouted,
+            _ => Self::Other(code),
+        }
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TrainInfo {
+    /// Train category and number
+    pub train_number: TrainNumber,
+    /// Station the train starts from.
+    /// Here and in the stops, stations abroad have an empty `id` (see `TrainStation`).
+    pub origin: TrainStation,
+    /// Station the train ends in
+    pub destination: TrainStation,
     pub current_station: TrainStation,
     pub current_delay: i16,
     pub is_at_station: bool,
+    /// Name of the place where the train was last detected, which may not be a station
+    pub last_detection_station: Option<String>,
+    /// Time when the train was last detected
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::rome_datetime::option"))]
+    pub last_detection_time: Option<chrono::DateTime<chrono_tz::Tz>>,
+    /// Cancellation or rerouting of the train
+    pub measure: TrainMeasure,
+    /// Whether the train has been rescheduled
+    pub rescheduled: bool,
+    /// Whether the train changes number along the way
+    pub changes_number: bool,
+    /// Notice about the train status, e.g. its delay or cancelled stops
+    pub sub_title: Option<String>,
     pub stops: Vec<DetailedTrainTripStop>,
 }
 
 impl TrainInfo {
-    pub fn from(vtvec: &Vec<mapping::VTDetailedTrainTripLeg>, trenitalia: &Trenitalia) -> Self {
-        let mut delay: i16 = 0;
-        let mut current_station: TrainStation = trenitalia
-            .find_train_station(&vtvec[&vtvec.len() - 1].stazione)
-            .unwrap()
-            .clone();
-        let mut in_station: bool = false;
+    pub fn from(status: &mapping::VTTrainStatus, trenitalia: &Trenitalia) -> Result<Self, Error> {
+        // Stations abroad are not in the station database
+        let find_station = |vt_id: Option<&str>, name: &str| {
+            viaggiatreno::known_station(trenitalia, vt_id, name)
+                .cloned()
+                .unwrap_or_else(|| TrainStation::unlisted(name, vt_id))
+        };
+        let to_datetime = |ts: Option<u64>| {
+            ts.map(|ts| utils::datetime_from_millis(ts as i64))
+                .transpose()
+        };
+        // The train is at the last stop it has reached, or at its origin if it hasn't left yet
+        let current_stop = status
+            .fermate
+            .iter()
+            .rev()
+            .find(|stop| stop.arrivoReale.is_some() || stop.partenzaReale.is_some())
+            .or_else(|| status.fermate.first())
+            .ok_or_else(|| Error::MalformedPayload("the train has no stops".to_string()))?;
         let mut stations_list: Vec<DetailedTrainTripStop> = Vec::new();
-        for stop in vtvec {
-            let this_station = trenitalia.find_train_station(&stop.stazione).unwrap();
-            if stop.stazioneCorrente {
-                current_station = this_station.clone();
-                if let Some(r) = stop.fermata.partenzaReale {
-                    if let Some(t) = stop.fermata.partenza_teorica {
-                        delay = ((r as i64 - t as i64) / 60000i64) as i16;
-                    }
-                } else if let Some(t) = stop.fermata.partenza_teorica {
-                    delay = (((chrono::Local::now().timestamp_millis() - t as i64) / 60000i64)
-                        as i16)
-                        .max(0);
-                }
-                if stop.fermata.arrivoReale.is_some() && stop.fermata.partenzaReale.is_none() {
-                    // If the train has arrived but not departed yet
-                    in_station = true;
-                }
-            }
+        for stop in &status.fermate {
             let this_stop = DetailedTrainTripStop {
-                arrival: stop
-                    .fermata
-                    .arrivoReale
-                    .map(|ts| chrono::Local.timestamp((ts / 1000) as i64, 0)),
-                departure: stop
-                    .fermata
-                    .partenzaReale
-                    .map(|ts| chrono::Local.timestamp((ts / 1000) as i64, 0)),
-                expected_arrival: stop
-                    .fermata
-                    .arrivo_teorico
-                    .map(|ts| chrono::Local.timestamp((ts / 1000) as i64, 0)),
-                expected_departure: stop
-                    .fermata
-                    .partenza_teorica
-                    .map(|ts| chrono::Local.timestamp((ts / 1000) as i64, 0)),
-                platform: stop
-                    .fermata
-                    .binarioEffettivoPartenzaDescrizione
-                    .as_ref()
-                    .unwrap_or(
-                        stop.fermata
-                            .binarioProgrammatoPartenzaDescrizione
-                            .as_ref()
-                            .unwrap_or(
-                                stop.fermata
-                                    .binarioEffettivoArrivoDescrizione
-                                    .as_ref()
-                                    .unwrap_or(
-                                        stop.fermata
-                                            .binarioProgrammatoArrivoDescrizione
-                                            .as_ref()
-                                            .unwrap_or(&"?".to_string()),
-                                    ),
-                            ),
-                    )
-                    .to_string(),
-                station: this_station.clone(),
+                arrival: to_datetime(stop.arrivoReale)?,
+                departure: to_datetime(stop.partenzaReale)?,
+                expected_arrival: to_datetime(stop.arrivo_teorico)?,
+                expected_departure: to_datetime(stop.partenza_teorica)?,
+                scheduled_arrival_platform: utils::parse_platform(
+                    &stop.binarioProgrammatoArrivoDescrizione,
+                ),
+                actual_arrival_platform: utils::parse_platform(
+                    &stop.binarioEffettivoArrivoDescrizione,
+                ),
+                scheduled_departure_platform: utils::parse_platform(
+                    &stop.binarioProgrammatoPartenzaDescrizione,
+                ),
+                actual_departure_platform: utils::parse_platform(
+                    &stop.binarioEffettivoPartenzaDescrizione,
+                ),
+                station: find_station(Some(&stop.id), &stop.stazione),
+                arrival_delay: stop.ritardoArrivo,
+                departure_delay: stop.ritardoPartenza,
+                kind: StopKind::from_api(stop.tipoFermata, stop.actualFermataType),
+                next_stop_changed: stop.isNextChanged || stop.nextChanged,
             };
             stations_list.push(this_stop);
         }
-        TrainInfo {
-            current_delay: delay,
-            is_at_station: in_station,
-            current_station: current_station,
+        let category = status
+            .categoriaDescrizione
+            .as_deref()
+            .map(str::trim)
+            .filter(|category| !category.is_empty())
+            .or(status.categoria.as_deref())
+            .unwrap_or("");
+        Ok(TrainInfo {
+            train_number: utils::match_train_type(category, status.numeroTreno),
+            origin: find_station(status.idOrigine.as_deref(), &status.origine),
+            destination: find_station(status.idDestinazione.as_deref(), &status.destinazione),
+            current_station: find_station(Some(&current_stop.id), &current_stop.stazione),
+            current_delay: status.ritardo,
+            // If the train has arrived but not departed yet
+            is_at_station: current_stop.arrivoReale.is_some()
+                && current_stop.partenzaReale.is_none(),
+            last_detection_station: status
+                .stazioneUltimoRilevamento
+                .as_deref()
+                .filter(|station| *station != "--")
+                .map(String::from),
+            last_detection_time: to_datetime(status.oraUltimoRilevamento)?,
+            measure: TrainMeasure::from(status.provvedimento),
+            rescheduled: status.riprogrammazione.as_deref() == Some("Y"),
+            changes_number: status.haCambiNumero,
+            sub_title: status
+                .subTitle
+                .as_deref()
+                .map(str::trim)
+                .filter(|sub_title| !sub_title.is_empty())
+                .map(String::from),
             stops: stations_list,
+        })
+    }
+}
+
+/// A station found by `Trenitalia::search_stations`
+#[derive(Debug, Clone)]
+pub struct StationMatch<'a> {
+    pub station: &'a TrainStation,
+    /// The name or alias of the station that matched the query
+    pub alias: &'a str,
+    /// Similarity between the alias and the query, from 0 to 1
+    pub score: f64,
+}
+
+/// Italian region, numbered with the ViaggiaTreno region codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Region {
+    Lombardia = 1,
+    Liguria = 2,
+    Piemonte = 3,
+    ValleDAosta = 4,
+    Lazio = 5,
+    Umbria = 6,
+    Molise = 7,
+    EmiliaRomagna = 8,
+    TrentinoAltoAdige = 9,
+    FriuliVeneziaGiulia = 10,
+    Marche = 11,
+    Veneto = 12,
+    Toscana = 13,
+    Sicilia = 14,
+    Basilicata = 15,
+    Puglia = 16,
+    Calabria = 17,
+    Campania = 18,
+    Abruzzo = 19,
+    Sardegna = 20,
+    Trento = 21,
+    Bolzano = 22,
+}
+
+impl Region {
+    /// Every region, sorted by code
+    pub const ALL: [Region; 22] = [
+        Self::Lombardia,
+        Self::Liguria,
+        Self::Piemonte,
+        Self::ValleDAosta,
+        Self::Lazio,
+        Self::Umbria,
+        Self::Molise,
+        Self::EmiliaRomagna,
+        Self::TrentinoAltoAdige,
+        Self::FriuliVeneziaGiulia,
+        Self::Marche,
+        Self::Veneto,
+        Self::Toscana,
+        Self::Sicilia,
+        Self::Basilicata,
+        Self::Puglia,
+        Self::Calabria,
+        Self::Campania,
+        Self::Abruzzo,
+        Self::Sardegna,
+        Self::Trento,
+        Self::Bolzano,
+    ];
+
+    /// Get the region with a ViaggiaTreno region code
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(id).checked_sub(1)?).copied()
+    }
+
+    /// Get the ViaggiaTreno region code
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Get the Italian name of the region
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lombardia => "Lombardia",
+            Self::Liguria => "Liguria",
+            Self::Piemonte => "Piemonte",
+            Self::ValleDAosta => "Valle d'Aosta",
+            Self::Lazio => "Lazio",
+            Self::Umbria => "Umbria",
+            Self::Molise => "Molise",
+            Self::EmiliaRomagna => "Emilia-Romagna",
+            Self::TrentinoAltoAdige => "Trentino-Alto Adige",
+            Self::FriuliVeneziaGiulia => "Friuli-Venezia Giulia",
+            Self::Marche => "Marche",
+            Self::Veneto => "Veneto",
+            Self::Toscana => "Toscana",
+            Self::Sicilia => "Sicilia",
+            Self::Basilicata => "Basilicata",
+            Self::Puglia => "Puglia",
+            Self::Calabria => "Calabria",
+            Self::Campania => "Campania",
+            Self::Abruzzo => "Abruzzo",
+            Self::Sardegna => "Sardegna",
+            Self::Trento => "Provincia autonoma di Trento",
+            Self::Bolzano => "Provincia autonoma di Bolzano",
+        }
+    }
+
+    /// Get the English name of the region
+    pub fn english_name(self) -> &'static str {
+        match self {
+            Self::Lombardia => "Lombardy",
+            Self::Liguria => "Liguria",
+            Self::Piemonte => "Piedmont",
+            Self::ValleDAosta => "Aosta Valley",
+            Self::Lazio => "Lazio",
+            Self::Umbria => "Umbria",
+            Self::Molise => "Molise",
+            Self::EmiliaRomagna => "Emilia-Romagna",
+            Self::TrentinoAltoAdige => "Trentino-South Tyrol",
+            Self::FriuliVeneziaGiulia => "Friuli-Venezia Giulia",
+            Self::Marche => "Marche",
+            Self::Veneto => "Veneto",
+            Self::Toscana => "Tuscany",
+            Self::Sicilia => "Sicily",
+            Self::Basilicata => "Basilicata",
+            Self::Puglia => "Apulia",
+            Self::Calabria => "Calabria",
+            Self::Campania => "Campania",
+            Self::Abruzzo => "Abruzzo",
+            Self::Sardegna => "Sardinia",
+            Self::Trento => "Autonomous Province of Trento",
+            Self::Bolzano => "Autonomous Province of Bolzano",
         }
     }
 }
 
+impl std::convert::TryFrom<u8> for Region {
+    type Error = crate::Error;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        Self::from_id(id).ok_or(crate::Error::UnknownRegion(id))
+    }
+}
+
+impl std::convert::From<Region> for u8 {
+    fn from(region: Region) -> Self {
+        region.id()
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// Struct that holds the train station data
+///
+/// It can always be serialized, even without the `serde` feature.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainStation {
-    /// Three-charachters ID
+    /// Three-charachters ID, empty for the stations that are not in the station database,
+    /// such as the ones abroad reached by international trains
     pub id: String,
-    /// Trenitalia region ID
+    /// ViaggiaTreno region ID (see `Region`)
     pub region_id: u8,
     /// Tuple that contains latitude and longitude
     pub position: (f64, f64),
@@ -262,9 +607,33 @@ impl TrainStation {
     pub fn short_id(&self) -> Option<String> {
         match &self.vt_id {
             None => None,
-            Some(x) => Some(str::replace(x, "S", "").parse::<u16>().unwrap().to_string()),
+            Some(x) => str::replace(x, "S", "")
+                .parse::<u16>()
+                .ok()
+                .map(|x| x.to_string()),
+        }
+    }
+    /// A station that is not in the station database, such as one abroad, described by one
+    /// of the APIs: it has an empty `id`, no region and no position
+    pub(crate) fn unlisted(name: &str, vt_id: Option<&str>) -> Self {
+        TrainStation {
+            id: String::new(),
+            region_id: 0,
+            position: (0.0, 0.0),
+            aliases: vec![name.to_string()],
+            vt_id: vt_id.map(String::from),
+            lefrecce_name: None,
         }
     }
+
+    /// Get the station's ID as a `StationCode`
+    pub fn code(&self) -> Result<crate::StationCode, crate::Error> {
+        crate::StationCode::parse(&self.id)
+    }
+    /// Get the station's region, if the region ID is a known one
+    pub fn region(&self) -> Option<Region> {
+        Region::from_id(self.region_id)
+    }
     /// Get the station's name (the first alias)
     pub fn get_name(&self) -> &str {
         &self.aliases[0]
@@ -1,48 +1,193 @@
-use crate::types::TrainNumber;
+//! Module which contains utilities
+use chrono::TimeZone;
 
-/// Module which contains utilities
+use crate::normalize::normalize;
+use crate::types::TrainNumber;
+use crate::Error;
 
-/// This function returns the normalized typing distance between two strings
+/// This function returns the normalized typing distance between two station names,
+/// compared in their normal form (see `normalize`)
 pub fn match_strings(first: &str, second: &str) -> f64 {
-    if first.to_lowercase() == second.to_lowercase() {
+    match_normalized(&normalize(first), &normalize(second))
+}
+
+/// Like `match_strings`, for names that are already in their normal form
+pub(crate) fn match_normalized(first: &str, second: &str) -> f64 {
+    if first == second {
         1.0
     } else {
-        strsim::normalized_damerau_levenshtein(&first.to_lowercase(), &second.to_lowercase())
+        strsim::normalized_damerau_levenshtein(first, second)
     }
 }
 
-macro_rules! current_timestamp_ms {
-    () => {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis()
-    };
+/// Interprets a date and time without offset as Italian time.
+///
+/// When the clocks go back the time is ambiguous and the first occurrence (summer time)
+/// is chosen; when the clocks go forward the skipped hour is shifted one hour later.
+pub fn rome_from_naive(naive: &chrono::NaiveDateTime) -> Option<chrono::DateTime<chrono_tz::Tz>> {
+    crate::TIMEZONE
+        .from_local_datetime(naive)
+        .earliest()
+        .or_else(|| {
+            crate::TIMEZONE
+                .from_local_datetime(&(*naive + chrono::Duration::hours(1)))
+                .earliest()
+        })
+}
+
+/// Parses a ViaggiaTreno date and time (`%FT%T`, Italian time)
+pub fn parse_rome_datetime(value: &str) -> Result<chrono::DateTime<chrono_tz::Tz>, Error> {
+    chrono::NaiveDateTime::parse_from_str(value, "%FT%T")
+        .ok()
+        .and_then(|naive| rome_from_naive(&naive))
+        .ok_or_else(|| Error::TimeParse(value.to_string()))
+}
+
+/// Parses a LeFrecce date and time with its UTC offset
+pub fn parse_offset_datetime(value: &str) -> Result<chrono::DateTime<chrono_tz::Tz>, Error> {
+    chrono::DateTime::parse_from_str(value, "%+")
+        .map(|datetime| datetime.with_timezone(&crate::TIMEZONE))
+        .map_err(|_| Error::TimeParse(value.to_string()))
+}
+
+/// Converts a timestamp in milliseconds to an Italian date and time
+pub fn datetime_from_millis(timestamp: i64) -> Result<chrono::DateTime<chrono_tz::Tz>, Error> {
+    crate::TIMEZONE
+        .timestamp_millis_opt(timestamp)
+        .single()
+        .ok_or_else(|| Error::TimeParse(timestamp.to_string()))
+}
+
+/// Parses a train number, replacing any non-digit character with a zero
+pub fn parse_train_number(value: &str) -> Result<u32, Error> {
+    value.parse::<u32>().or_else(|_| {
+        value
+            .chars()
+            .map(|x| if x.is_ascii_digit() { x } else { '0' })
+            .collect::<String>()
+            .parse::<u32>()
+            .map_err(|_| Error::MalformedPayload(format!("invalid train number: {}", value)))
+    })
 }
 
 /// Builds a TrainNumber enum from the train number and train type
 pub fn match_train_type(description: &str, number: u32) -> TrainNumber {
-    let train_type = match description {
-        "RV" => TrainNumber::RegionaleVeloce { number: number },
-        "Regionale" => TrainNumber::Regionale { number: number },
-        "Frecciarossa" => TrainNumber::FrecciaRossa { number: number },
-        "Frecciaargento" => TrainNumber::FrecciaArgento { number: number },
-        "IC" => TrainNumber::InterCity { number: number },
-        "Frecciabianca" => TrainNumber::FrecciaBianca { number: number },
-        "ICN" => TrainNumber::InterCityNotte { number: number },
-        "EN" => TrainNumber::EuroNight { number: number },
-        "EC" => TrainNumber::EuroCity { number: number },
-        "REG" => TrainNumber::Regionale { number: number },
-        "Autobus" => TrainNumber::Bus { number: number },
-        "BUS" => TrainNumber::Bus { number: number },
-        "FR" => TrainNumber::FrecciaRossa { number: number },
-        "FA" => TrainNumber::FrecciaArgento { number: number },
-        "FB" => TrainNumber::FrecciaBianca { number: number },
-        "ECB" => TrainNumber::EuroCity { number: number },
+    match description {
+        "RV" => TrainNumber::RegionaleVeloce { number },
+        "Regionale" => TrainNumber::Regionale { number },
+        "Frecciarossa" => TrainNumber::FrecciaRossa { number },
+        "Frecciaargento" => TrainNumber::FrecciaArgento { number },
+        "IC" => TrainNumber::InterCity { number },
+        "Frecciabianca" => TrainNumber::FrecciaBianca { number },
+        "ICN" => TrainNumber::InterCityNotte { number },
+        "EN" => TrainNumber::EuroNight { number },
+        "EC" => TrainNumber::EuroCity { number },
+        "REG" => TrainNumber::Regionale { number },
+        "Autobus" => TrainNumber::Bus { number },
+        "BUS" => TrainNumber::Bus { number },
+        "FR" => TrainNumber::FrecciaRossa { number },
+        "FA" => TrainNumber::FrecciaArgento { number },
+        "FB" => TrainNumber::FrecciaBianca { number },
+        "ECB" => TrainNumber::EuroCity { number },
         _ => TrainNumber::Unknown {
-            number: number,
+            number,
             name: String::from(description),
         },
-    };
-    train_type
+    }
+}
+
+/// Converts a platform returned by ViaggiaTreno, which may be blank or padded with spaces
+pub fn parse_platform(platform: &Option<String>) -> Option<String> {
+    platform
+        .as_deref()
+        .map(str::trim)
+        .filter(|platform| !platform.is_empty())
+        .map(String::from)
+}
+
+/// Serde helpers for Italian dates and times, stored as RFC 3339 strings
+#[cfg(feature = "serde")]
+pub mod rome_datetime {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    type DateTime = chrono::DateTime<chrono_tz::Tz>;
+
+    pub fn serialize<S: Serializer>(value: &DateTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        chrono::DateTime::parse_from_rfc3339(&value)
+            .map(|datetime| datetime.with_timezone(&crate::TIMEZONE))
+            .map_err(serde::de::Error::custom)
+    }
+
+    /// For `Option<DateTime>` fields
+    pub mod option {
+        use super::DateTime;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper(#[serde(with = "super")] DateTime);
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<DateTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            value.map(Wrapper).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime>, D::Error> {
+            Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(value)| value))
+        }
+    }
+
+    /// For `(TrainStation, DateTime)` fields
+    pub mod station {
+        use super::DateTime;
+        use crate::TrainStation;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        #[derive(Serialize)]
+        struct Borrowed<'a>(&'a TrainStation, #[serde(with = "super")] &'a DateTime);
+
+        #[derive(Deserialize)]
+        struct Owned(TrainStation, #[serde(with = "super")] DateTime);
+
+        pub fn serialize<S: Serializer>(
+            value: &(TrainStation, DateTime),
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            Borrowed(&value.0, &value.1).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<(TrainStation, DateTime), D::Error> {
+            let Owned(station, datetime) = Owned::deserialize(deserializer)?;
+            Ok((station, datetime))
+        }
+    }
+}
+
+/// Serde helpers for durations, stored as a number of seconds
+#[cfg(feature = "serde")]
+pub mod duration_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &chrono::Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(value.num_seconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<chrono::Duration, D::Error> {
+        Ok(chrono::Duration::seconds(i64::deserialize(deserializer)?))
+    }
 }
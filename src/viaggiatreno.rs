@@ -0,0 +1,373 @@
+use std::convert::TryFrom;
+
+use chrono::SubsecRound;
+
+use crate::types::{BoardEntry, Region, TrainStation, TrainTrip};
+use crate::{mapping, utils, Error, Journey, RemoteStation, Trenitalia, WORDS_EQUALITY_THRESHOLD};
+
+/// A piece of a ViaggiaTreno solution
+pub enum SolutionPart {
+    /// A train returned by ViaggiaTreno
+    Trip(TrainTrip),
+    /// A stretch that ViaggiaTreno doesn't cover and that has to be searched on LeFrecce
+    Gap(Gap),
+}
+
+/// A stretch of a solution to be covered with a LeFrecce search
+pub struct Gap {
+    pub from: TrainStation,
+    pub to: TrainStation,
+    /// Time used for the LeFrecce search
+    pub when: chrono::DateTime<chrono_tz::Tz>,
+    /// The filling trains can't leave before this time
+    pub after: chrono::DateTime<chrono_tz::Tz>,
+    /// The filling trains must arrive before this time, if any
+    pub before: Option<chrono::DateTime<chrono_tz::Tz>>,
+}
+
+impl Gap {
+    /// Returns the trains of the first LeFrecce solution that fits in the gap
+    pub fn fill(&self, solutions: &[Journey]) -> Vec<TrainTrip> {
+        solutions
+            .iter()
+            .find(|solution| {
+                solution.departure_time() >= self.after
+                    && self
+                        .before
+                        .is_none_or(|before| solution.arrival_time() <= before)
+            })
+            .map(|solution| solution.legs().to_vec())
+            .unwrap_or_default()
+    }
+}
+
+/// Builds the URL of the journey search between two stations
+pub fn solutions_url(
+    trenitalia: &Trenitalia,
+    from: &TrainStation,
+    to: &TrainStation,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> Result<String, Error> {
+    Ok(trenitalia.viaggiatreno_url(&format!(
+        "soluzioniViaggioNew/{}/{}/{}",
+        from.short_id()
+            .ok_or_else(|| Error::UnknownStation(from.get_name().to_string()))?,
+        to.short_id()
+            .ok_or_else(|| Error::UnknownStation(to.get_name().to_string()))?,
+        when.with_timezone(&crate::TIMEZONE).format("%FT%T")
+    )))
+}
+
+/// Converts a ViaggiaTreno solution to a list of trains, leaving gaps where the
+/// solution doesn't start from `from`, doesn't end in `to` or requires moving between stations
+pub fn plan_solution(
+    trenitalia: &Trenitalia,
+    soluzione: &mapping::VTTrainSolution,
+    from: &TrainStation,
+    to: &TrainStation,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> Result<Vec<SolutionPart>, Error> {
+    let (first_vehicle, last_vehicle) =
+        match (soluzione.vehicles.first(), soluzione.vehicles.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(vec![]),
+        };
+    let mut parts: Vec<SolutionPart> = Vec::new();
+    let first_origin = first_vehicle.origine.as_deref().unwrap_or("");
+    if utils::match_strings(first_origin, from.get_name()) < WORDS_EQUALITY_THRESHOLD {
+        let filling_to = trenitalia.station_from_api_name(first_vehicle.origine.as_ref())?;
+        parts.push(SolutionPart::Gap(Gap {
+            from: from.clone(),
+            to: filling_to.clone(),
+            when: *when,
+            after: when.trunc_subsecs(0),
+            before: Some(utils::parse_rome_datetime(&first_vehicle.orarioPartenza)?),
+        }));
+    }
+    let mut old_to: Option<&TrainStation> = None;
+    let mut old_ts = when.trunc_subsecs(0);
+    for train_trip in soluzione.vehicles.iter() {
+        let from = trenitalia.station_from_api_name(train_trip.origine.as_ref())?;
+        let to = trenitalia.station_from_api_name(train_trip.destinazione.as_ref())?;
+        let departure = utils::parse_rome_datetime(&train_trip.orarioPartenza)?;
+        let arrival = utils::parse_rome_datetime(&train_trip.orarioArrivo)?;
+        if let Some(old_to) = old_to {
+            if old_to.get_name() != from.get_name() {
+                parts.push(SolutionPart::Gap(Gap {
+                    from: old_to.clone(),
+                    to: from.clone(),
+                    when: old_ts,
+                    after: old_ts,
+                    before: Some(departure),
+                }));
+            }
+        }
+        old_to = Some(to);
+        old_ts = arrival;
+        parts.push(SolutionPart::Trip(TrainTrip {
+            departure: (from.clone(), departure),
+            arrival: (to.clone(), arrival),
+            train_number: utils::match_train_type(
+                &train_trip.categoriaDescrizione,
+                utils::parse_train_number(&train_trip.numeroTreno)?,
+            ),
+        }));
+    }
+    let last_destination = last_vehicle.destinazione.as_deref().unwrap_or("");
+    if utils::match_strings(last_destination, to.get_name()) < WORDS_EQUALITY_THRESHOLD {
+        let filling_from = trenitalia.station_from_api_name(last_vehicle.destinazione.as_ref())?;
+        parts.push(SolutionPart::Gap(Gap {
+            from: filling_from.clone(),
+            to: to.clone(),
+            when: *when,
+            after: utils::parse_rome_datetime(&last_vehicle.orarioArrivo)?,
+            before: None,
+        }));
+    }
+    Ok(parts)
+}
+
+/// Returns the station with a ViaggiaTreno ID or, when the ID is missing or unknown, with
+/// exactly the given name (compared in its normal form)
+pub fn known_station<'a>(
+    trenitalia: &'a Trenitalia,
+    vt_id: Option<&str>,
+    name: &str,
+) -> Option<&'a TrainStation> {
+    vt_id
+        .and_then(|vt_id| trenitalia.get_train_station_by_vt_id(vt_id))
+        .or_else(|| trenitalia.stations.by_name(name))
+}
+
+/// Returns the known station matching the first result of the station autocompletion
+pub fn station_from_autocomplete<'a>(
+    trenitalia: &'a Trenitalia,
+    response: &str,
+) -> Option<&'a TrainStation> {
+    let first_vt_id = response
+        .lines()
+        .next()
+        .and_then(|line| line.split('|').nth(1))?
+        .trim();
+    trenitalia.get_train_station_by_vt_id(first_vt_id)
+}
+
+/// Builds the URL of the region lookup of a station
+pub fn region_url(trenitalia: &Trenitalia, station: &TrainStation) -> Result<String, Error> {
+    let vt_id = station
+        .vt_id
+        .as_deref()
+        .ok_or_else(|| Error::UnknownStation(station.get_name().to_string()))?;
+    Ok(trenitalia.viaggiatreno_url(&format!("regione/{}", vt_id)))
+}
+
+/// Parses the response of the region lookup, which is the bare region code
+pub fn parse_region(response: &str) -> Result<Region, Error> {
+    let id = response
+        .trim()
+        .parse::<u8>()
+        .map_err(|_| Error::MalformedPayload(format!("invalid region: {}", response)))?;
+    Region::try_from(id)
+}
+
+/// Builds the URL of the station list of a region
+pub fn station_list_url(trenitalia: &Trenitalia, region: Region) -> String {
+    trenitalia.viaggiatreno_url(&format!("elencoStazioni/{}", region.id()))
+}
+
+/// Parses the station list of a region
+pub fn parse_station_list(response: &str) -> Result<Vec<RemoteStation>, Error> {
+    let entries: Vec<mapping::VTStationListEntry> = serde_json::from_str(response)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| RemoteStation {
+            vt_id: entry.codiceStazione,
+            name: entry.localita.nomeLungo,
+            region_id: entry.codReg,
+            position: (entry.lat, entry.lon),
+        })
+        .collect())
+}
+
+/// Builds the URL of the train number search
+pub fn train_search_url(trenitalia: &Trenitalia, number: u32) -> String {
+    trenitalia.viaggiatreno_url(&format!("cercaNumeroTrenoTrenoAutocomplete/{}", number))
+}
+
+/// A train returned by the train number search
+pub struct TrainCandidate {
+    /// Name of the station of origin
+    pub origin: String,
+    /// ViaggiaTreno ID of the station of origin
+    pub station_code: String,
+    /// Departure date, as the timestamp of midnight in milliseconds, if returned by the API
+    pub date: Option<i64>,
+}
+
+/// Parses the train number search result
+pub fn parse_train_candidates(response: &str) -> Result<Vec<TrainCandidate>, Error> {
+    response
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.split('|').collect();
+            let origin = fields[0].split('-').nth(1);
+            let codes: Vec<&str> = fields
+                .get(1)
+                .map(|x| x.split('-').map(str::trim).collect())
+                .unwrap_or_default();
+            match (origin, codes.get(1)) {
+                (Some(origin), Some(station_code)) => Ok(TrainCandidate {
+                    origin: origin.trim().to_string(),
+                    station_code: station_code.to_string(),
+                    date: codes.get(2).and_then(|date| date.parse().ok()),
+                }),
+                _ => Err(Error::MalformedPayload(format!(
+                    "unexpected train search result: {}",
+                    line
+                ))),
+            }
+        })
+        .collect()
+}
+
+/// Picks the train whose station of origin best matches `from`
+pub fn select_train_origin<'a>(
+    candidates: &'a [TrainCandidate],
+    number: u32,
+    from: &str,
+) -> Result<&'a TrainCandidate, Error> {
+    match candidates.len() {
+        1 => Ok(&candidates[0]),
+        0 => Err(Error::TrainNotFound(number)),
+        _ => {
+            let mut selected = &candidates[0];
+            let mut min_diff = 0.0;
+            for candidate in candidates {
+                let diff =
+                    utils::match_strings(&candidate.origin.to_lowercase(), &from.to_lowercase());
+                if diff > min_diff {
+                    min_diff = diff;
+                    selected = candidate;
+                }
+                if diff == 1.0 {
+                    break;
+                }
+            }
+            if min_diff == 0.0 {
+                Err(Error::AmbiguousTrain(number))
+            } else {
+                Ok(selected)
+            }
+        }
+    }
+}
+
+/// Builds the URL of the train status.
+/// Trains without a departure date are looked up among the ones leaving today.
+pub fn train_info_url(
+    trenitalia: &Trenitalia,
+    number: u32,
+    candidate: &TrainCandidate,
+) -> Result<String, Error> {
+    let date = match candidate.date {
+        Some(date) => date,
+        None => {
+            let today = chrono::Utc::now()
+                .with_timezone(&crate::TIMEZONE)
+                .date_naive()
+                .and_time(chrono::NaiveTime::MIN);
+            utils::rome_from_naive(&today)
+                .ok_or_else(|| Error::TimeParse(today.to_string()))?
+                .timestamp_millis()
+        }
+    };
+    Ok(trenitalia.viaggiatreno_url(&format!(
+        "andamentoTreno/{}/{}/{}",
+        candidate.station_code, number, date
+    )))
+}
+
+/// One of the boards of a station
+#[derive(Clone, Copy)]
+pub enum Board {
+    Departures,
+    Arrivals,
+}
+
+/// Builds the URL of a station board, listing the trains from `when` onwards
+pub fn board_url(
+    trenitalia: &Trenitalia,
+    board: Board,
+    station: &TrainStation,
+    when: &chrono::DateTime<chrono_tz::Tz>,
+) -> Result<String, Error> {
+    let vt_id = station
+        .vt_id
+        .as_ref()
+        .ok_or_else(|| Error::UnknownStation(station.get_name().to_string()))?;
+    // The API wants the date as formatted by JavaScript's Date.toString()
+    let date = when
+        .with_timezone(&crate::TIMEZONE)
+        .format("%a %b %d %Y %H:%M:%S GMT%z")
+        .to_string()
+        .replace(' ', "%20")
+        .replace('+', "%2B");
+    Ok(trenitalia.viaggiatreno_url(&format!(
+        "{}/{}/{}",
+        match board {
+            Board::Departures => "partenze",
+            Board::Arrivals => "arrivi",
+        },
+        vt_id,
+        date
+    )))
+}
+
+/// Converts the entries of a station board
+pub fn parse_board(
+    trenitalia: &Trenitalia,
+    board: Board,
+    response: &str,
+) -> Result<Vec<BoardEntry>, Error> {
+    let entries: Vec<mapping::VTBoardEntry> = serde_json::from_str(response)?;
+    entries
+        .iter()
+        .map(|entry| {
+            // Only the origin comes with its ViaggiaTreno ID
+            let (station_id, station_name, time, scheduled_platform, actual_platform) = match board
+            {
+                Board::Departures => (
+                    None,
+                    &entry.destinazione,
+                    entry.orarioPartenza,
+                    &entry.binarioProgrammatoPartenzaDescrizione,
+                    &entry.binarioEffettivoPartenzaDescrizione,
+                ),
+                Board::Arrivals => (
+                    entry.codOrigine.as_deref(),
+                    &entry.origine,
+                    entry.orarioArrivo,
+                    &entry.binarioProgrammatoArrivoDescrizione,
+                    &entry.binarioEffettivoArrivoDescrizione,
+                ),
+            };
+            let station_name = station_name.as_deref().unwrap_or("").trim();
+            let time = time.ok_or_else(|| {
+                Error::MalformedPayload(format!("train {} has no time", entry.numeroTreno))
+            })?;
+            Ok(BoardEntry {
+                train_number: utils::match_train_type(
+                    entry.categoriaDescrizione.as_deref().unwrap_or("").trim(),
+                    entry.numeroTreno,
+                ),
+                station: known_station(trenitalia, station_id, station_name).cloned(),
+                station_name: station_name.to_string(),
+                scheduled_time: utils::datetime_from_millis(time)?,
+                delay: entry.ritardo,
+                scheduled_platform: utils::parse_platform(scheduled_platform),
+                actual_platform: utils::parse_platform(actual_platform),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,266 @@
+//! Polling of a running train, reporting what changed between two polls
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::types::{DetailedTrainTripStop, StopKind, TrainInfo, TrainMeasure, TrainStation};
+use crate::{Error, Trenitalia};
+
+/// Source of the current time, and of the waits between two polls
+pub trait Clock: Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> chrono::DateTime<chrono_tz::Tz>;
+    /// Blocks the current thread for `duration`
+    fn sleep(&self, duration: std::time::Duration);
+}
+
+/// Default clock, which uses the system time
+#[derive(Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> chrono::DateTime<chrono_tz::Tz> {
+        chrono::Utc::now().with_timezone(&crate::TIMEZONE)
+    }
+    fn sleep(&self, duration: std::time::Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+impl<T: Clock + ?Sized> Clock for Arc<T> {
+    fn now(&self) -> chrono::DateTime<chrono_tz::Tz> {
+        (**self).now()
+    }
+    fn sleep(&self, duration: std::time::Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// How to tell the watched train apart from other trains with the same number
+#[derive(Debug, Clone)]
+pub enum WatchedTrain {
+    /// The train starting from the station with this name
+    From(String),
+    /// The train calling at this station
+    CallingAt(TrainStation),
+}
+
+/// A change in the status of the watched train
+#[derive(Debug, Clone)]
+pub enum TrainEvent {
+    /// The delay of the train, in minutes, has changed
+    DelayChanged { from: i16, to: i16 },
+    /// The train arrives at or leaves from a platform other than the scheduled one
+    PlatformChanged {
+        station: TrainStation,
+        scheduled: String,
+        actual: String,
+    },
+    /// The train won't stop at this station anymore
+    StopCancelled { station: TrainStation },
+    /// The train has left this station
+    Departed {
+        station: TrainStation,
+        time: chrono::DateTime<chrono_tz::Tz>,
+    },
+    /// The train has reached this station
+    Arrived {
+        station: TrainStation,
+        time: chrono::DateTime<chrono_tz::Tz>,
+    },
+}
+
+/// Polls a train at a regular interval and yields the changes in its status
+///
+/// The first poll only records the status of the train. The iterator ends once the train
+/// has reached its terminus or has been cancelled; failed polls are yielded as errors and
+/// the next poll is retried after the usual interval.
+pub struct TrainWatcher<'a> {
+    trenitalia: &'a Trenitalia,
+    number: u32,
+    train: WatchedTrain,
+    interval: std::time::Duration,
+    clock: Arc<dyn Clock>,
+    last_poll: Option<chrono::DateTime<chrono_tz::Tz>>,
+    last_info: Option<TrainInfo>,
+    pending: VecDeque<TrainEvent>,
+    finished: bool,
+}
+
+impl<'a> TrainWatcher<'a> {
+    pub fn new(
+        trenitalia: &'a Trenitalia,
+        number: u32,
+        train: WatchedTrain,
+        interval: std::time::Duration,
+    ) -> Self {
+        TrainWatcher {
+            trenitalia,
+            number,
+            train,
+            interval,
+            clock: Arc::new(SystemClock),
+            last_poll: None,
+            last_info: None,
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// Uses a custom clock instead of the system one
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The status of the train at the last successful poll
+    pub fn last_info(&self) -> Option<&TrainInfo> {
+        self.last_info.as_ref()
+    }
+
+    /// Whether the train has reached its terminus or has been cancelled
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Fetches the status of the train right away and returns what changed since the last poll
+    pub fn poll(&mut self) -> Result<Vec<TrainEvent>, Error> {
+        let (trenitalia, number, train) = self.start_poll();
+        let info = match train {
+            WatchedTrain::From(origin) => trenitalia.train_info(number, origin),
+            WatchedTrain::CallingAt(station) => trenitalia.train_info_calling_at(number, &station),
+        }?;
+        Ok(self.update(info))
+    }
+
+    /// Records the time of a poll and returns what's needed to fetch the status of the train
+    pub(crate) fn start_poll(&mut self) -> (&'a Trenitalia, u32, WatchedTrain) {
+        self.last_poll = Some(self.clock.now());
+        (self.trenitalia, self.number, self.train.clone())
+    }
+
+    /// Records a new status of the train and returns what changed since the previous one
+    pub(crate) fn update(&mut self, info: TrainInfo) -> Vec<TrainEvent> {
+        let events = match &self.last_info {
+            Some(last_info) => diff(last_info, &info),
+            None => vec![],
+        };
+        // A partially cancelled train ends at its last stop that isn't cancelled
+        self.finished = info.measure == TrainMeasure::Cancelled
+            || info
+                .stops
+                .iter()
+                .rev()
+                .find(|stop| stop.kind != StopKind::Cancelled)
+                .is_some_and(|terminus| terminus.arrival.is_some());
+        self.last_info = Some(info);
+        events
+    }
+}
+
+impl Iterator for TrainWatcher<'_> {
+    type Item = Result<TrainEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.finished {
+                return None;
+            }
+            if let Some(last_poll) = self.last_poll {
+                let elapsed = (self.clock.now() - last_poll).to_std().unwrap_or_default();
+                self.clock.sleep(self.interval.saturating_sub(elapsed));
+            }
+            match self.poll() {
+                Ok(events) => self.pending.extend(events),
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// Returns the platform change at a stop, as (scheduled, actual) platforms
+fn changed_platform(stop: &DetailedTrainTripStop) -> Option<(String, String)> {
+    if !stop.platform_changed() {
+        return None;
+    }
+    let departure = (
+        &stop.scheduled_departure_platform,
+        &stop.actual_departure_platform,
+    );
+    let arrival = (
+        &stop.scheduled_arrival_platform,
+        &stop.actual_arrival_platform,
+    );
+    [departure, arrival]
+        .iter()
+        .find_map(|(scheduled, actual)| match (scheduled, actual) {
+            (Some(scheduled), Some(actual)) if scheduled != actual => {
+                Some((scheduled.clone(), actual.clone()))
+            }
+            _ => None,
+        })
+}
+
+/// Whether two stops of a train are at the same station. The stations abroad all have an
+/// empty `id`, so the ViaggiaTreno ID is compared first.
+fn same_station(a: &TrainStation, b: &TrainStation) -> bool {
+    match (&a.vt_id, &b.vt_id) {
+        (Some(a), Some(b)) => a == b,
+        _ if a.id.is_empty() || b.id.is_empty() => a.get_name() == b.get_name(),
+        _ => a.id == b.id,
+    }
+}
+
+/// Compares two statuses of the same train
+fn diff(old: &TrainInfo, new: &TrainInfo) -> Vec<TrainEvent> {
+    let mut events: Vec<TrainEvent> = Vec::new();
+    for stop in &new.stops {
+        let old_stop = old
+            .stops
+            .iter()
+            .find(|old_stop| same_station(&old_stop.station, &stop.station));
+        if let Some(time) = stop.arrival {
+            if old_stop.is_none_or(|old_stop| old_stop.arrival.is_none()) {
+                events.push(TrainEvent::Arrived {
+                    station: stop.station.clone(),
+                    time,
+                });
+            }
+        }
+        if let Some(change) = changed_platform(stop) {
+            if old_stop.and_then(changed_platform).as_ref() != Some(&change) {
+                let (scheduled, actual) = change;
+                events.push(TrainEvent::PlatformChanged {
+                    station: stop.station.clone(),
+                    scheduled,
+                    actual,
+                });
+            }
+        }
+        if stop.kind == StopKind::Cancelled
+            && old_stop.is_some_and(|old_stop| old_stop.kind != StopKind::Cancelled)
+        {
+            events.push(TrainEvent::StopCancelled {
+                station: stop.station.clone(),
+            });
+        }
+        if let Some(time) = stop.departure {
+            if old_stop.is_none_or(|old_stop| old_stop.departure.is_none()) {
+                events.push(TrainEvent::Departed {
+                    station: stop.station.clone(),
+                    time,
+                });
+            }
+        }
+    }
+    if old.current_delay != new.current_delay {
+        events.push(TrainEvent::DelayChanged {
+            from: old.current_delay,
+            to: new.current_delay,
+        });
+    }
+    events
+}